@@ -0,0 +1,81 @@
+//! a bean-seeking bot playing through the JSON bot API
+//! usage: cargo run --example json_bot -- <host ip>:14515 [name]
+
+use serde_json::{ json, Value };
+
+use std::env;
+use std::io::{ BufRead, BufReader, Write };
+use std::net::TcpStream;
+
+const DIRECTIONS: [(&str, isize, isize); 4] = [("L", 0, -1), ("R", 0, 1), ("U", -1, 0), ("D", 1, 0)];
+
+fn coord(v: &Value) -> (isize, isize) {
+    (v[0].as_i64().unwrap_or(0) as isize, v[1].as_i64().unwrap_or(0) as isize)
+}
+
+/// where a step goes, `None` if it leaves the yard
+fn step(state: &Value, from: (isize, isize), dr: isize, dc: isize) -> Option<(isize, isize)> {
+    let height = state["height"].as_i64()? as isize;
+    let width = state["width"].as_i64()? as isize;
+    let (r, c) = (from.0 + dr, from.1 + dc);
+    if state["wrap_around"].as_bool() == Some(true) {
+        Some((r.rem_euclid(height), c.rem_euclid(width)))
+    } else if (0..height).contains(&r) && (0..width).contains(&c) {
+        Some((r, c))
+    } else {
+        None
+    }
+}
+
+/// go to the nearest bean, avoiding walls and bodies on the next block
+fn decide(state: &Value, me: u64) -> Option<&'static str> {
+    let snakes = state["snakes"].as_array()?;
+    let snake = snakes.iter().find(|s| s["id"].as_u64() == Some(me))?;
+    let head = coord(&snake["body"][0]);
+    let mut blocked: Vec<(isize, isize)> = state["walls"].as_array()?.iter().map(coord).collect();
+    for s in snakes {
+        blocked.extend(s["body"].as_array()?.iter().map(coord));
+    }
+    let beans: Vec<(isize, isize)> = state["beans"].as_array()?.iter().map(coord).collect();
+    let distance = |c: (isize, isize)| beans.iter()
+        .map(|b| (b.0 - c.0).abs() + (b.1 - c.1).abs())
+        .min()
+        .unwrap_or(0);
+    DIRECTIONS.iter()
+        .filter_map(|(d, dr, dc)| step(state, head, *dr, *dc).map(|c| (*d, c)))
+        .filter(|(_d, c)| !blocked.contains(c))
+        .min_by_key(|(_d, c)| distance(*c))
+        .map(|(d, _c)| d)
+}
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let addr = args.get(1).map(String::as_str).unwrap_or("127.0.0.1:14515");
+    let name = args.get(2).map(String::as_str).unwrap_or("JSON Bot");
+    let mut stream = TcpStream::connect(addr)?;
+    let reader = BufReader::new(stream.try_clone()?);
+    let id: u64 = std::process::id() as u64;
+    writeln!(stream, "{}", json!({ "NewSnake": [id, name, null] }))?;
+
+    let mut me = None; // the number of the snake on the field
+    for line in reader.lines() {
+        let info: Value = match serde_json::from_str(&line?) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if let Some(spawned) = info.get("Spawned") {
+            if spawned[0].as_u64() == Some(id) {
+                me = spawned[1].as_u64();
+                println!("Spawned as snake {}", me.unwrap_or(0));
+            }
+        } else if let Some(state) = info.get("State") {
+            if let Some(d) = me.and_then(|me| decide(state, me)) {
+                writeln!(stream, "{}", json!({ "CtrlSnake": [id, d] }))?;
+            }
+        } else if info.get("Failed").and_then(Value::as_u64) == Some(id) {
+            println!("Failed, waiting for the next round if there is any");
+            me = None;
+        }
+    }
+    Ok(())
+}
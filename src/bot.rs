@@ -0,0 +1,183 @@
+//! pub mod bot: computer controlled players
+//! a bot looks at the snapshot of the yard each tick, and decides where its snake goes
+
+use crate::yard::{ Coord, Direction, YardState, SnakeState, BotLevel };
+
+use std::collections::VecDeque;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+const DIRECTIONS: [Direction; 4] = [Direction::L, Direction::R, Direction::U, Direction::D];
+
+/// something that controls a snake by the state of the yard
+pub trait Bot: Send {
+    /// decide where the snake `me` goes, `None` to keep going
+    fn decide(&mut self, state: &YardState, me: u8) -> Option<Direction>;
+}
+
+/// create a bot of the level
+pub fn new_bot(level: BotLevel) -> Box<dyn Bot> {
+    bot_with_rng(level, StdRng::from_entropy())
+}
+
+/// create a bot of the level that always decides the same way in the same game
+pub fn new_seeded_bot(level: BotLevel, seed: u64) -> Box<dyn Bot> {
+    bot_with_rng(level, StdRng::seed_from_u64(seed))
+}
+
+fn bot_with_rng(level: BotLevel, rng: StdRng) -> Box<dyn Bot> {
+    match level {
+        BotLevel::Easy => Box::new(RandomWalker { rng }),
+        BotLevel::Normal => Box::new(GreedySeeker { rng }),
+        BotLevel::Hard => Box::new(Pathfinder { rng }),
+    }
+}
+
+/// moves that don't kill on the next tick, with where they lead
+fn safe_moves(state: &YardState, s: &SnakeState, blocked: &[Vec<bool>]) -> Vec<(Direction, Coord)> {
+    DIRECTIONS.iter()
+        .filter(|d| **d != s.direction.opposite())
+        .filter_map(|d| state.next_pos(s.body[0], *d).map(|c| (*d, c)))
+        .filter(|(_d, c)| !blocked[c.0][c.1])
+        .collect()
+}
+
+/// blocks between two coordinates, going around the edges if possible
+fn distance(state: &YardState, a: Coord, b: Coord) -> usize {
+    let dr = a.0.abs_diff(b.0);
+    let dc = a.1.abs_diff(b.1);
+    if state.wrap_around {
+        dr.min(state.height - dr) + dc.min(state.width - dc)
+    } else {
+        dr + dc
+    }
+}
+
+/// count the blocks reachable from `start`, stop counting at `limit`
+fn room_from(state: &YardState, start: Coord, blocked: &[Vec<bool>], limit: usize) -> usize {
+    let mut seen = vec![vec![false; state.width]; state.height];
+    let mut queue = VecDeque::from([start]);
+    seen[start.0][start.1] = true;
+    let mut count = 0;
+    while let Some(c) = queue.pop_front() {
+        count += 1;
+        if count >= limit {
+            break;
+        }
+        for d in DIRECTIONS {
+            if let Some(n) = state.next_pos(c, d) {
+                if !blocked[n.0][n.1] && !seen[n.0][n.1] {
+                    seen[n.0][n.1] = true;
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+    count
+}
+
+/// also block where the other heads may go next, to avoid head-on collisions
+fn cautious(state: &YardState, me: u8, blocked: &[Vec<bool>]) -> Vec<Vec<bool>> {
+    let mut cautious = blocked.to_vec();
+    for s in state.snakes.iter().filter(|s| s.id != me && !s.stalled) {
+        for d in DIRECTIONS {
+            if let Some(c) = state.next_pos(s.body[0], d) {
+                cautious[c.0][c.1] = true;
+            }
+        }
+    }
+    cautious
+}
+
+/// goes straight, and turns randomly now and then or when it is about to die
+pub struct RandomWalker {
+    rng: StdRng,
+}
+
+impl Bot for RandomWalker {
+    fn decide(&mut self, state: &YardState, me: u8) -> Option<Direction> {
+        let s = state.snake(me)?;
+        let safe = safe_moves(state, s, &state.obstacles(me));
+        let d = if self.rng.gen_ratio(1, 5) { s.direction.next_random_with(&mut self.rng) } else { s.direction };
+        if safe.iter().any(|m| m.0 == d) {
+            Some(d)
+        } else {
+            safe.choose(&mut self.rng).map(|m| m.0)
+        }
+    }
+}
+
+/// heads to the nearest bean without looking further than the next block
+pub struct GreedySeeker {
+    rng: StdRng,
+}
+
+impl Bot for GreedySeeker {
+    fn decide(&mut self, state: &YardState, me: u8) -> Option<Direction> {
+        let s = state.snake(me)?;
+        let mut safe = safe_moves(state, s, &state.obstacles(me));
+        safe.shuffle(&mut self.rng);
+        let head = s.body[0];
+        let target = state.beans.iter().min_by_key(|b| distance(state, head, **b));
+        match target {
+            Some(t) => safe.iter().min_by_key(|m| distance(state, m.1, *t)).map(|m| m.0),
+            None => safe.first().map(|m| m.0),
+        }
+    }
+}
+
+/// takes the shortest path to a bean, as long as it does not get trapped there
+pub struct Pathfinder {
+    rng: StdRng,
+}
+
+impl Pathfinder {
+    /// breadth first search for the nearest bean, returns the first move
+    fn path_to_bean(state: &YardState, s: &SnakeState, blocked: &[Vec<bool>]) -> Option<Direction> {
+        let mut first_move = vec![vec![None; state.width]; state.height];
+        let mut queue = VecDeque::new();
+        for (d, c) in safe_moves(state, s, blocked) {
+            first_move[c.0][c.1] = Some(d);
+            queue.push_back(c);
+        }
+        while let Some(c) = queue.pop_front() {
+            if state.beans.contains(&c) {
+                return first_move[c.0][c.1];
+            }
+            for d in DIRECTIONS {
+                if let Some(n) = state.next_pos(c, d) {
+                    if !blocked[n.0][n.1] && first_move[n.0][n.1].is_none() && n != s.body[0] {
+                        first_move[n.0][n.1] = first_move[c.0][c.1];
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Bot for Pathfinder {
+    fn decide(&mut self, state: &YardState, me: u8) -> Option<Direction> {
+        let s = state.snake(me)?;
+        let mut blocked = cautious(state, me, &state.obstacles(me));
+        if safe_moves(state, s, &blocked).is_empty() {
+            blocked = state.obstacles(me); // no choice but taking the risk
+        }
+        let mut safe = safe_moves(state, s, &blocked);
+        safe.shuffle(&mut self.rng);
+        // enough room to hold the whole body is enough
+        let need = s.body.len() + 1;
+        let roomy: Vec<&(Direction, Coord)> = safe.iter()
+            .filter(|m| room_from(state, m.1, &blocked, need) >= need)
+            .collect();
+        match Pathfinder::path_to_bean(state, s, &blocked) {
+            Some(d) if roomy.iter().any(|m| m.0 == d) => Some(d),
+            // otherwise get to where there is most room
+            _ => safe.iter()
+                .max_by_key(|m| room_from(state, m.1, &blocked, state.width * state.height))
+                .map(|m| m.0),
+        }
+    }
+}
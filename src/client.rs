@@ -1,107 +1,500 @@
 /// pub mod client: have a fn that can be started as a thread
 /// interact with the server and user
 
-use crate::render;
-use crate::server::{ YardCtrl, YardInfo };
-use crate::yard::Direction;
+use crate::render::{ Frontend, Color };
+use crate::server::{ YardCtrl, YardInfo, BoardType };
+use crate::yard::{ YardState, MatchMode, BOOST_COST };
+use crate::transport::{ ClientTransport, LinkStats };
+use crate::controller::{ Controller, Input, InputRecorder };
+use crate::predict::Predictor;
+use crate::names::RenameReason;
 
-use crossterm::event::{ poll, read, Event, KeyCode };
 use rand::{ thread_rng, Rng };
 
 use std::thread;
-use std::time::Duration;
-use std::sync::mpsc::{ Sender, Receiver, TryRecvError };
+use std::time::{ Duration, Instant };
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::mpsc::{ self, Sender, Receiver };
+use std::path::Path;
+use std::fs::File;
 
-/// polling keyboard strike and customize control
-/// id: client id, ctrl_tx: control signal channel
-pub fn polling_keyboard(id: u64, ctrl_tx: Sender<YardCtrl>) {
+/// a player at this client, playing a snake of their own
+#[derive(Clone, Debug)]
+pub struct Seat {
+    /// the client id of the snake
+    pub id: u64,
+    /// the name given by the server, and the one asked for
+    pub name: String,
+    pub wanted: String,
+    /// why the server gave another name, if it did
+    pub renamed: Option<RenameReason>,
+    /// the field id, while the snake is alive
+    pub me: Option<u8>,
+    /// out of an endless game for good
+    pub out: bool,
+}
+
+impl Seat {
+    /// a player with a fresh client id, not joined yet
+    pub fn new(name: String) -> Seat {
+        Seat {
+            id: thread_rng().gen_range(u64::MIN..u64::MAX),
+            name: name.trim().to_string(),
+            wanted: name,
+            renamed: None,
+            me: None,
+            out: false,
+        }
+    }
+
+    /// the note telling the name was changed by the server, `None` if it was not
+    fn renamed_note(&self, several: bool) -> Option<String> {
+        let why = match self.renamed? {
+            RenameReason::Sanitized => "the name wanted had characters not allowed or was too long",
+            RenameReason::Taken => "the name wanted was taken",
+        };
+        let who = if several { format!("{} plays", self.wanted.trim()) } else { String::from("you play") };
+        Some(format!("({} as {}, {})", who, self.name, why))
+    }
+}
+
+/// polling the controller and send its inputs to the server
+/// ids: client ids by seat, the controller observes as the first one, info_rx: infos for the controller to observe,
+/// transport: where control signals go, local_tx: inputs the ui shows itself, such as turns
+/// to be shown before the server takes them, quit: set when the user quits or the server is gone
+pub fn polling_controller(
+        ids: Vec<u64>,
+        mut controller: Box<dyn Controller>,
+        info_rx: Receiver<YardInfo>,
+        transport: Arc<dyn ClientTransport>,
+        local_tx: Sender<Input>,
+        quit: Arc<AtomicBool>,
+    ) {
     loop {
-        if poll(Duration::from_millis(10)).unwrap_or_else(|e| { println!("Err {}", e); false }) {
-            match read().unwrap() {
-                Event::Key(event) => {
-                    match event.code {
-                        KeyCode::Left | KeyCode::Char('a') => {
-                            ctrl_tx.send(YardCtrl::CtrlSnake(id, Direction::L)).unwrap();
-                        },
-                        KeyCode::Right | KeyCode::Char('d') => {
-                            ctrl_tx.send(YardCtrl::CtrlSnake(id, Direction::R)).unwrap();
-                        },
-                        KeyCode::Up | KeyCode::Char('w') => {
-                            ctrl_tx.send(YardCtrl::CtrlSnake(id, Direction::U)).unwrap();
-                        },
-                        KeyCode::Down | KeyCode::Char('s') => {
-                            ctrl_tx.send(YardCtrl::CtrlSnake(id, Direction::D)).unwrap();
-                        },
-                        KeyCode::Esc => {
-                            ctrl_tx.send(YardCtrl::QuitGame).unwrap_or(());
-                            // Err if singleplayer and backend already quitted
-                            return;
-                        },
-                        _ => {},
-                    };
-                },
-                Event::Mouse(_event) => {},
-                Event::Resize(_width, _height) => {},
-            }
+        while let Ok(info) = info_rx.try_recv() {
+            controller.observe(ids[0], &info);
+        }
+        let (seat, input) = match controller.poll_seat(Duration::from_millis(10)) {
+            Some((seat, input)) if seat < ids.len() => (seat, input),
+            _ => continue,
+        };
+        let id = ids[seat];
+        match input {
+            Input::Turn(d) => {
+                if transport.send_ctrl(YardCtrl::CtrlSnake(id, d)).is_err() {
+                    break;
+                }
+                // only the first player's turns are guessed
+                if seat == 0 {
+                    local_tx.send(Input::Turn(d)).unwrap_or(()); // the ui may have finished
+                }
+            },
+            Input::Boost => {
+                if transport.send_ctrl(YardCtrl::Boost(id)).is_err() {
+                    break;
+                }
+            },
+            Input::Chat(text) => {
+                if transport.send_ctrl(YardCtrl::Chat(id, text)).is_err() {
+                    break;
+                }
+                local_tx.send(Input::Typing(None)).unwrap_or(());
+            },
+            input @ Input::ToggleHud | input @ Input::Typing(_) => {
+                local_tx.send(input).unwrap_or(());
+            },
+            Input::Quit => {
+                quit.store(true, Ordering::Relaxed);
+                transport.send_ctrl(YardCtrl::QuitGame).unwrap_or(());
+                // Err if singleplayer and backend already quitted
+                return;
+            },
+        }
+    }
+    // the server is gone, the ui finishes as if the user quitted
+    quit.store(true, Ordering::Relaxed);
+}
+
+/// the footer telling how much boost the snake `me` has left
+fn boost_footer(state: &YardState, me: u8) -> Option<String> {
+    let s = state.snake(me)?;
+    Some(if s.effects.boost > 0 {
+        format!("Boosting! {} ticks left {}", s.effects.boost, "=".repeat(s.effects.boost as usize / 4))
+    } else {
+        format!("Boost ready, for {} blocks of length", BOOST_COST)
+    })
+}
+
+/// the boost footers of the players alive, named if there are several
+fn seats_footer(state: &YardState, seats: &[Seat]) -> Option<String> {
+    let lines: Vec<String> = seats.iter()
+        .filter_map(|s| boost_footer(state, s.me?).map(|f| match seats.len() {
+            1 => f,
+            _ => format!("{}: {}", s.name, f),
+        }))
+        .collect();
+    if lines.is_empty() { None } else { Some(lines.join("\n")) }
+}
+
+/// how often the round trip is measured while the HUD is shown
+pub const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// the diagnostics line, telling whether a bad game was the network or the code
+#[derive(Default)]
+struct Hud {
+    shown: bool,
+    /// pings not answered yet, with when they were sent
+    pings: Vec<(u64, Instant)>,
+    last_ping: Option<Instant>,
+    rtt: Option<Duration>,
+    /// when the frames of the last second came
+    frames: VecDeque<Instant>,
+    /// the last tick of the server seen
+    tick: u64,
+}
+
+impl Hud {
+    /// measure the round trip now and then, while shown
+    fn ping(&mut self, transport: &dyn ClientTransport) {
+        let now = Instant::now();
+        if !self.shown || self.last_ping.is_some_and(|t| now.duration_since(t) < PING_INTERVAL) {
+            return;
+        }
+        // pongs of others may come too, so the number is random
+        let n = thread_rng().gen();
+        if transport.send_ctrl(YardCtrl::Ping(n)).is_ok() {
+            // the unanswered ones are given up after a while
+            self.pings.retain(|p| now.duration_since(p.1) < PING_INTERVAL * 5);
+            self.pings.push((n, now));
+        }
+        self.last_ping = Some(now);
+    }
+
+    fn pong(&mut self, n: u64) {
+        if let Some(i) = self.pings.iter().position(|p| p.0 == n) {
+            self.rtt = Some(self.pings.remove(i).1.elapsed());
+        }
+    }
+
+    fn frame(&mut self) {
+        let now = Instant::now();
+        self.frames.push_back(now);
+        while self.frames.front().is_some_and(|t| now.duration_since(*t) > Duration::from_secs(1)) {
+            self.frames.pop_front();
+        }
+    }
+
+    fn line(&self, link: LinkStats) -> String {
+        if !self.shown {
+            return String::new();
+        }
+        let rtt = match self.rtt {
+            Some(rtt) => format!("{} ms", rtt.as_millis()),
+            None => String::from("-"),
+        };
+        format!(
+            "RTT {} | {} fps | lost {} | late {} | doubled {} | tick {}",
+            rtt, self.frames.len(), link.dropped, link.out_of_order, link.duplicated, self.tick,
+        )
+    }
+}
+
+/// the chat panel keeps this many messages
+pub const CHAT_LINES: usize = 5;
+
+/// the last messages of the chat, and what the player is typing
+#[derive(Default)]
+struct ChatPanel {
+    lines: VecDeque<(Color, String)>,
+    draft: Option<String>,
+}
+
+impl ChatPanel {
+    fn push(&mut self, color: Color, line: String) {
+        self.lines.push_back((color, line));
+        if self.lines.len() > CHAT_LINES {
+            self.lines.pop_front();
         }
     }
+
+    fn board(&self) -> BoardType {
+        let mut board: BoardType = self.lines.iter().cloned().collect();
+        if let Some(draft) = &self.draft {
+            board.push((Color::Black, format!("> {}_", draft)));
+        }
+        board
+    }
 }
 
 /// checking if buffer is sended by the server, and print
-pub fn polling_buf(id: u64, mut ui: render::TUIHelper, info_rx: Receiver<YardInfo>) {
+/// gives back the ui when the game is over for every seat, for the recording
+///  - `seats`: the players here, the first one's turns are guessed before the server takes them
+///  - `rounds`: the game is played in rounds, failing only ends this round
+///  - `info_tx`: the infos are passed on to the controller
+///  - `local_rx`: inputs the ui shows itself, such as turns before the server takes them
+pub fn polling_buf(
+        mut seats: Vec<Seat>,
+        mut ui: Box<dyn Frontend>,
+        transport: Arc<dyn ClientTransport>,
+        info_tx: Sender<YardInfo>,
+        local_rx: Receiver<Input>,
+        mut rounds: bool,
+        quit: Arc<AtomicBool>,
+    ) -> Box<dyn Frontend> {
+    let mut predictor = Predictor::new(seats[0].me);
+    let mut hud = Hud::default();
+    let mut chat = ChatPanel::default();
+    // how the game went for the players here, and the host's best, shown when it is over
+    let mut summary = BoardType::new();
+    let mut leaderboard = BoardType::new();
+    // renamed while waiting for the others to join
+    for note in seats.iter().filter_map(|s| s.renamed_note(seats.len() > 1)) {
+        chat.push(Color::Black, note);
+        ui.set_chat(&chat.board()).unwrap();
+    }
     loop {
-        match info_rx.try_recv() {
-            Ok(info) => {
+        if quit.load(Ordering::Relaxed) {
+            return ui;
+        }
+        while let Ok(input) = local_rx.try_recv() {
+            match input {
+                Input::Turn(d) if predictor.turn(d) => {
+                    if let Some(buf) = predictor.view() {
+                        ui.draw_yard(buf).unwrap();
+                    }
+                },
+                Input::ToggleHud => {
+                    hud.shown = !hud.shown;
+                    ui.set_hud(&hud.line(transport.link_stats()));
+                },
+                Input::Typing(draft) => {
+                    chat.draft = draft;
+                    ui.set_chat(&chat.board()).unwrap();
+                },
+                _ => {},
+            }
+        }
+        hud.ping(transport.as_ref());
+        match transport.recv_info(Duration::from_millis(10)) {
+            Ok(Some(info)) => {
+                info_tx.send(info.clone()).unwrap_or(()); // the controller may have quitted
                 match info {
                     YardInfo::RefreshScreen(buf) => {
-                        ui.refresh_yard(buf).unwrap();
+                        hud.frame();
+                        // with a turn guessed, the frame is drawn when its state comes
+                        let pending = predictor.pending();
+                        predictor.frame(buf.clone());
+                        if !pending {
+                            ui.draw_yard(buf).unwrap();
+                        }
                     },
                     YardInfo::Board(s) => {
-                        ui.print_board(&s).unwrap();
+                        ui.set_hud(&hud.line(transport.link_stats()));
+                        ui.draw_board(&s).unwrap();
+                    },
+                    YardInfo::Pong(n) => {
+                        hud.pong(n);
+                    },
+                    YardInfo::Chat(color, name, text) => {
+                        chat.push(color, format!("{}: {}", name, text));
+                        ui.set_chat(&chat.board()).unwrap();
+                    },
+                    YardInfo::Renamed(rid, name, reason) => {
+                        let several = seats.len() > 1;
+                        if let Some(seat) = seats.iter_mut().find(|s| s.id == rid) {
+                            seat.name = name;
+                            seat.renamed = Some(reason);
+                            if let Some(note) = seat.renamed_note(several) {
+                                chat.push(Color::Black, note);
+                                ui.set_chat(&chat.board()).unwrap();
+                            }
+                        }
+                    },
+                    YardInfo::ChatRefused(rid) if rid == seats[0].id => {
+                        chat.push(Color::Black, String::from("(slow down, the message was not sent)"));
+                        ui.set_chat(&chat.board()).unwrap();
+                    },
+                    YardInfo::Config(c) => {
+                        rounds = c.match_mode != MatchMode::Endless;
+                        ui.set_wrap_around(c.wrap_around).unwrap();
+                    },
+                    YardInfo::Summary(rid, lines) if seats.iter().any(|s| s.id == rid) => {
+                        summary.extend(lines);
+                    },
+                    YardInfo::Leaderboard(board) => {
+                        leaderboard = board;
+                    },
+                    YardInfo::Spawned(rid, fid) => {
+                        if let Some(i) = seats.iter().position(|s| s.id == rid) {
+                            seats[i].me = Some(fid);
+                            if i == 0 {
+                                predictor.set_me(Some(fid));
+                            }
+                        }
+                    },
+                    YardInfo::State(s) => {
+                        hud.tick = s.tick;
+                        if let Some(footer) = seats_footer(&s, &seats) {
+                            ui.set_footer(&footer);
+                        }
+                        let pending = predictor.pending();
+                        predictor.reconcile(s);
+                        if pending {
+                            if let Some(buf) = predictor.view() {
+                                ui.draw_yard(buf).unwrap();
+                            }
+                        }
                     },
-                    YardInfo::Failed(fid) => {
-                        if fid == id {
-                            ui.print_info(
-                                    "Oops, try next time! press ESC to return to the menu."
-                                ).unwrap();
-                            return;
+                    YardInfo::Failed(rid) => {
+                        let i = match seats.iter().position(|s| s.id == rid) {
+                            Some(i) => i,
+                            None => continue,
+                        };
+                        seats[i].me = None;
+                        seats[i].out = !rounds;
+                        if i == 0 {
+                            predictor.set_me(None);
                         }
+                        if rounds && seats.iter().all(|s| s.me.is_none()) {
+                            ui.set_footer(
+                                    "Out for this round, wait for the next one! press ESC to return to the menu."
+                                );
+                        } else if seats.iter().all(|s| s.out) {
+                            let over: String = summary.iter().chain(&leaderboard).map(|l| l.1.as_str()).collect();
+                            ui.game_over(&format!(
+                                    "{}Oops, try next time! press ESC to return to the menu.", over,
+                                )).unwrap();
+                            return ui;
+                        } else {
+                            chat.push(Color::Black, format!("({} is out)", seats[i].name));
+                            ui.set_chat(&chat.board()).unwrap();
+                        }
+                    },
+                    YardInfo::Countdown(secs) => {
+                        ui.set_footer("");
+                        ui.show_info(&format!("Get ready! The round starts in {} ...", secs)).unwrap();
+                    },
+                    YardInfo::RoundOver(mut board) => {
+                        board.append(&mut summary);
+                        board.append(&mut leaderboard);
+                        ui.round_over(&board).unwrap();
                     },
                     _ => {},
                 };
             },
-            Err(TryRecvError::Empty) => {},
-            Err(TryRecvError::Disconnected) => { return; },
+            Ok(None) => {},
+            Err(_e) => { return ui; },
         }
     }
 }
 
+/// tell why the game can't go on, and wait for the user to return to the menu
+fn give_up(ui: &mut Box<dyn Frontend>, controller: &mut Box<dyn Controller>, why: &str) {
+    ui.game_over(&format!("{}. press ESC to return to the menu.", why)).unwrap_or(());
+    while !matches!(controller.poll(Duration::from_millis(100)), Some(Input::Quit)) {}
+}
+
 /// client main procedure
+///  - `names`: the players here, a snake each, more than one if they share the keyboard
+///  - `team`: the team wanted, if teams are played
+///  - `controller`: who plays the snakes
+///  - `ui`: what the game is shown on
+///  - `record`: where to save the recording of the frontend, if wanted, the inputs are saved besides
 pub fn start_and_play(
-        name: String,
-        info_rx: Receiver<YardInfo>,
-        ctrl_tx: Sender<YardCtrl>,
+        names: Vec<String>,
+        team: Option<u8>,
+        transport: Arc<dyn ClientTransport>,
+        mut controller: Box<dyn Controller>,
+        mut ui: Box<dyn Frontend>,
+        record: Option<String>,
     ) {
-    let ui = render::TUIHelper::new();
-    let id: u64 = thread_rng().gen_range(u64::MIN..u64::MAX);
-    ctrl_tx.send(YardCtrl::NewSnake(id, name)).unwrap();
+    let mut seats: Vec<Seat> = names.into_iter().map(Seat::new).collect();
+    for s in &seats {
+        if let Err(e) = transport.send_ctrl(YardCtrl::NewSnake(s.id, s.name.clone(), team)) {
+            give_up(&mut ui, &mut controller, &format!("Can't join, {}", e));
+            return;
+        }
+    }
+    if let Some(path) = &record {
+        let inputs_path = Path::new(path).with_extension("inputs");
+        match File::create(&inputs_path) {
+            Ok(file) => { controller = Box::new(InputRecorder::new(controller, file)); },
+            Err(e) => println!("Failed to record the inputs {}", e),
+        }
+    }
+    let (info_tx, info_rx) = mpsc::channel();
+    let (local_tx, local_rx) = mpsc::channel();
+    let mut rounds = false;
+    let mut waiting: Vec<u64> = seats.iter().map(|s| s.id).collect();
     loop {
-        match info_rx.recv().unwrap() {
-            YardInfo::RegisteredSnake(rid, result) => {
-                if rid == id && result {
+        let info = match transport.recv_info(Duration::from_millis(100)) {
+            Ok(info) => info,
+            Err(e) => {
+                give_up(&mut ui, &mut controller, &format!("Lost the server before joining, {}", e));
+                return;
+            },
+        };
+        if let Some(info) = &info {
+            info_tx.send(info.clone()).unwrap();
+        }
+        match info {
+            Some(YardInfo::RegisteredSnake(rid, result)) => {
+                if result {
+                    waiting.retain(|w| *w != rid);
+                }
+                if waiting.is_empty() {
                     break;
                 }
             },
+            Some(YardInfo::Refused(rid, reason)) if waiting.contains(&rid) => {
+                // everyone plays or no one does
+                for s in &seats {
+                    transport.send_ctrl(YardCtrl::LeaveGame(s.id)).unwrap_or(());
+                }
+                let who = match seats.iter().find(|s| s.id == rid) {
+                    Some(s) if seats.len() > 1 => format!("{} can't join", s.name),
+                    _ => String::from("Can't join"),
+                };
+                give_up(&mut ui, &mut controller, &format!("{}, {}", who, reason));
+                return;
+            },
+            Some(YardInfo::Spawned(rid, fid)) => {
+                if let Some(s) = seats.iter_mut().find(|s| s.id == rid) {
+                    s.me = Some(fid);
+                }
+            },
+            Some(YardInfo::Renamed(rid, name, reason)) => {
+                if let Some(s) = seats.iter_mut().find(|s| s.id == rid) {
+                    s.name = name;
+                    s.renamed = Some(reason);
+                }
+            },
+            Some(YardInfo::Config(c)) => {
+                rounds = c.match_mode != MatchMode::Endless;
+                ui.set_wrap_around(c.wrap_around).unwrap();
+            },
             _ => {},
         }
     };
+    let quit = Arc::new(AtomicBool::new(false));
+    let quit_clone = Arc::clone(&quit);
+    let transport_clone = Arc::clone(&transport);
+    let ids = seats.iter().map(|s| s.id).collect();
     let refresing_handle = thread::spawn(move || {
-        polling_buf(id, ui, info_rx);
+        polling_buf(seats, ui, transport_clone, info_tx, local_rx, rounds, quit_clone)
     });
-    let keyboard_handle = thread::spawn(move || {
-        polling_keyboard(id, ctrl_tx);
+    let controller_handle = thread::spawn(move || {
+        polling_controller(ids, controller, info_rx, transport, local_tx, quit);
     });
 
-    refresing_handle.join().unwrap();
-    keyboard_handle.join().unwrap();
+    let ui = refresing_handle.join().unwrap();
+    controller_handle.join().unwrap();
+    if let Some(path) = record {
+        match ui.save_recording(&path) {
+            Ok(_) => println!("Match recorded to {}", path),
+            Err(e) => println!("Failed to save the recording {}", e),
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,401 @@
+//! pub mod controller: something that produces directions for a snake
+//! the client loop asks the controller for inputs and shows it what the server tells,
+//! so the same loop is driven by the keyboard, a bot or a script
+//!
+//! a script is plain text, one input each line, taking effect at the tick of the yard:
+//! ```text
+//! # lines beginning with '#' are comments
+//! 12 L
+//! 20 U
+//! 25 boost
+//! 300 quit
+//! ```
+//! the inputs of a match can be recorded as a script, see `InputRecorder`, playing it again sends
+//! the same inputs at the same ticks, but the beans and bots are random, so the match goes otherwise
+
+use crate::bot::{ self, Bot };
+use crate::keys::KeyBindings;
+use crate::server::{ YardInfo, MAX_CHAT_LEN };
+use crate::yard::{ Direction, YardState, BotLevel };
+
+use crossterm::event::{ poll, read, Event, KeyCode };
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{ self, File };
+use std::io::Write;
+use std::thread;
+use std::time::{ Duration, Instant };
+
+/// what a controller wants the snake to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    Turn(Direction),
+    /// trade some length for a burst of speed
+    Boost,
+    /// show or hide the network diagnostics
+    ToggleHud,
+    /// a chat message being typed so far, `None` when typing is over
+    Typing(Option<String>),
+    /// say something to everyone
+    Chat(String),
+    /// leave the game
+    Quit,
+}
+
+/// something that produces directions for a snake
+pub trait Controller: Send {
+    /// learn what the server tells, `id` is the client id of the snake controlled
+    fn observe(&mut self, _id: u64, _info: &YardInfo) {}
+    /// wait at most `timeout` for the next input, `None` if there is none
+    fn poll(&mut self, timeout: Duration) -> Option<Input>;
+    /// as `poll`, with the seat of the player the input is for, when players share the controller
+    /// the first seat is 0, and the only one unless told otherwise
+    fn poll_seat(&mut self, timeout: Duration) -> Option<(usize, Input)> {
+        self.poll(timeout).map(|input| (0, input))
+    }
+}
+
+/// the player at the keyboard, see `KeyBindings` for the keys
+#[derive(Default)]
+pub struct KeyboardController {
+    keys: KeyBindings,
+    /// the chat message being typed
+    draft: Option<String>,
+}
+
+impl KeyboardController {
+    pub fn new(keys: KeyBindings) -> KeyboardController {
+        KeyboardController { keys, draft: None }
+    }
+
+    /// keys go to the chat message while typing
+    fn type_key(&mut self, code: KeyCode) -> Option<Input> {
+        let draft = self.draft.as_mut()?;
+        match code {
+            KeyCode::Enter => {
+                let text = self.draft.take().unwrap();
+                Some(if text.trim().is_empty() { Input::Typing(None) } else { Input::Chat(text) })
+            },
+            KeyCode::Esc => {
+                self.draft = None;
+                Some(Input::Typing(None))
+            },
+            KeyCode::Backspace => {
+                draft.pop();
+                Some(Input::Typing(Some(draft.clone())))
+            },
+            KeyCode::Char(c) if draft.chars().count() < MAX_CHAT_LEN => {
+                draft.push(c);
+                Some(Input::Typing(Some(draft.clone())))
+            },
+            _ => None,
+        }
+    }
+
+    /// what a key pressed means, typing or bound
+    fn key(&mut self, code: KeyCode) -> Option<Input> {
+        if self.draft.is_some() {
+            return self.type_key(code);
+        }
+        let input = self.keys.action(code);
+        if let Some(Input::Typing(draft)) = &input {
+            self.draft = draft.clone();
+        }
+        input
+    }
+}
+
+/// wait at most `timeout` for a key pressed
+fn read_key(timeout: Duration) -> Option<KeyCode> {
+    if !poll(timeout).unwrap_or_else(|e| { println!("Err {}", e); false }) {
+        return None;
+    }
+    match read().unwrap() {
+        Event::Key(event) => Some(event.code),
+        Event::Mouse(_event) => None,
+        Event::Resize(_width, _height) => None,
+    }
+}
+
+impl Controller for KeyboardController {
+    fn poll(&mut self, timeout: Duration) -> Option<Input> {
+        self.key(read_key(timeout)?)
+    }
+}
+
+/// players sharing the keyboard, each seat navigating with keys of its own, see `keys::check_shared`
+/// the keys to chat, show the diagnostics and quit are the first player's, for everyone
+pub struct SharedKeyboard {
+    first: KeyboardController,
+    /// the keys of the other seats, from the second
+    others: Vec<KeyBindings>,
+}
+
+impl SharedKeyboard {
+    /// `players`: how many players, at most as many as the seats
+    /// `keys`: where the keys to chat, show the diagnostics and quit are taken from
+    /// `seats`: the keys to navigate and boost of each seat, as in the settings
+    pub fn new(players: usize, keys: &KeyBindings, seats: &[KeyBindings]) -> SharedKeyboard {
+        let mut layouts = seats.iter().take(players.max(1)).cloned();
+        let first = KeyBindings {
+            chat: keys.chat.clone(),
+            hud: keys.hud.clone(),
+            quit: keys.quit.clone(),
+            ..layouts.next().unwrap_or_default()
+        };
+        SharedKeyboard {
+            first: KeyboardController::new(first),
+            others: layouts.collect(),
+        }
+    }
+}
+
+impl Controller for SharedKeyboard {
+    /// the inputs of any seat, without telling which
+    fn poll(&mut self, timeout: Duration) -> Option<Input> {
+        self.poll_seat(timeout).map(|s| s.1)
+    }
+
+    fn poll_seat(&mut self, timeout: Duration) -> Option<(usize, Input)> {
+        let code = read_key(timeout)?;
+        if let Some(input) = self.first.key(code) {
+            return Some((0, input));
+        }
+        for (i, keys) in self.others.iter().enumerate() {
+            match keys.action(code) {
+                Some(input @ Input::Turn(_)) | Some(input @ Input::Boost) => { return Some((i + 1, input)); },
+                _ => {},
+            }
+        }
+        None
+    }
+}
+
+/// a bot deciding on each state of the yard
+pub struct BotController {
+    bot: Box<dyn Bot>,
+    /// the field id of the snake, when it is alive
+    me: Option<u8>,
+    /// the latest state not decided on yet
+    state: Option<YardState>,
+}
+
+impl BotController {
+    pub fn new(bot: Box<dyn Bot>) -> BotController {
+        BotController { bot, me: None, state: None }
+    }
+}
+
+impl Controller for BotController {
+    fn observe(&mut self, id: u64, info: &YardInfo) {
+        match info {
+            YardInfo::Spawned(rid, fid) if *rid == id => { self.me = Some(*fid); },
+            YardInfo::Failed(rid) if *rid == id => { self.me = None; },
+            YardInfo::State(s) => { self.state = Some(s.clone()); },
+            _ => {},
+        }
+    }
+
+    fn poll(&mut self, timeout: Duration) -> Option<Input> {
+        match (self.me, self.state.take()) {
+            (Some(me), Some(state)) => self.bot.decide(&state, me).map(Input::Turn),
+            _ => {
+                thread::sleep(timeout);
+                None
+            },
+        }
+    }
+}
+
+/// reasons why a script can't be loaded
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(std::io::Error),
+    /// a line that is not understood, with the line number
+    BadLine(usize, String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Io(e)
+                => write!(f, "can't read the script: {}", e),
+            ScriptError::BadLine(line, l)
+                => write!(f, "line {}: expected a tick and L, R, U, D, boost or quit, found \"{}\"", line, l),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// inputs written down in a script, see the module document
+#[derive(Clone, Debug)]
+pub struct ScriptedController {
+    inputs: VecDeque<(u64, Input)>,
+    tick: u64,
+}
+
+impl ScriptedController {
+    /// parse the script format
+    pub fn parse(source: &str) -> Result<ScriptedController, ScriptError> {
+        let mut inputs = VecDeque::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad_line = || ScriptError::BadLine(i + 1, line.to_string());
+            let (tick, input) = line.split_once(char::is_whitespace).ok_or_else(bad_line)?;
+            let tick = tick.parse::<u64>().map_err(|_e| bad_line())?;
+            let input = match input.trim() {
+                "L" => Input::Turn(Direction::L),
+                "R" => Input::Turn(Direction::R),
+                "U" => Input::Turn(Direction::U),
+                "D" => Input::Turn(Direction::D),
+                "boost" => Input::Boost,
+                "quit" => Input::Quit,
+                _ => { return Err(bad_line()); },
+            };
+            inputs.push_back((tick, input));
+        }
+        Ok(ScriptedController { inputs, tick: 0 })
+    }
+
+    /// read and parse a script file
+    pub fn load(path: &str) -> Result<ScriptedController, ScriptError> {
+        ScriptedController::parse(&fs::read_to_string(path).map_err(ScriptError::Io)?)
+    }
+}
+
+impl Controller for ScriptedController {
+    fn observe(&mut self, _id: u64, info: &YardInfo) {
+        if let YardInfo::State(s) = info {
+            self.tick = s.tick;
+        }
+    }
+
+    fn poll(&mut self, timeout: Duration) -> Option<Input> {
+        match self.inputs.front() {
+            Some((tick, _input)) if *tick <= self.tick => self.inputs.pop_front().map(|i| i.1),
+            _ => {
+                thread::sleep(timeout);
+                None
+            },
+        }
+    }
+}
+
+/// writes down the inputs of another controller as a script, to be played again later
+pub struct InputRecorder {
+    inner: Box<dyn Controller>,
+    file: File,
+    tick: u64,
+}
+
+impl InputRecorder {
+    pub fn new(inner: Box<dyn Controller>, mut file: File) -> InputRecorder {
+        writeln!(file, "# socket snake inputs, play them again by choosing this file as a script")
+            .unwrap_or_else(|e| println!("Failed to record the input {}", e));
+        InputRecorder { inner, file, tick: 0 }
+    }
+}
+
+impl Controller for InputRecorder {
+    fn observe(&mut self, id: u64, info: &YardInfo) {
+        if let YardInfo::State(s) = info {
+            self.tick = s.tick;
+        }
+        self.inner.observe(id, info);
+    }
+
+    fn poll(&mut self, timeout: Duration) -> Option<Input> {
+        self.poll_seat(timeout).map(|s| s.1)
+    }
+
+    /// only the first seat is recorded, a script plays one snake
+    fn poll_seat(&mut self, timeout: Duration) -> Option<(usize, Input)> {
+        let seated = self.inner.poll_seat(timeout);
+        let written = match &seated {
+            Some((0, Input::Turn(d))) => writeln!(self.file, "{} {:?}", self.tick, d),
+            Some((0, Input::Boost)) => writeln!(self.file, "{} boost", self.tick),
+            Some((_seat, Input::Quit)) => writeln!(self.file, "{} quit", self.tick),
+            // the chat is not part of the script
+            _ => Ok(()),
+        };
+        written.unwrap_or_else(|e| println!("Failed to record the input {}", e));
+        seated
+    }
+}
+
+/// another controller plays, while the keyboard can still take over or quit
+pub struct Autopilot {
+    pilot: Box<dyn Controller>,
+    keyboard: KeyboardController,
+}
+
+impl Autopilot {
+    pub fn new(pilot: Box<dyn Controller>, keys: KeyBindings) -> Autopilot {
+        Autopilot { pilot, keyboard: KeyboardController::new(keys) }
+    }
+}
+
+impl Controller for Autopilot {
+    fn observe(&mut self, id: u64, info: &YardInfo) {
+        self.pilot.observe(id, info);
+    }
+
+    fn poll(&mut self, timeout: Duration) -> Option<Input> {
+        let start = Instant::now();
+        match self.keyboard.poll(timeout / 2) {
+            Some(input) => Some(input),
+            None => self.pilot.poll(timeout.saturating_sub(start.elapsed())),
+        }
+    }
+}
+
+/// who plays the snake, as chosen in the menu
+#[derive(Clone, Debug)]
+pub enum ControllerChoice {
+    Keyboard,
+    Bot(BotLevel),
+    /// the name of the script, and the script
+    Script(String, ScriptedController),
+    /// you and friends at the same keyboard, with the friends' names
+    Shared(Vec<String>),
+}
+
+impl ControllerChoice {
+    pub fn describe(&self) -> String {
+        match self {
+            ControllerChoice::Keyboard => String::from("you"),
+            ControllerChoice::Bot(level) => format!("a bot ({})", level.name()),
+            ControllerChoice::Script(name, _script) => format!("the script {}", name),
+            ControllerChoice::Shared(friends) => format!("you and {}, sharing the keyboard", friends.join(", ")),
+        }
+    }
+
+    /// the names of the players, one snake each, the first is yours
+    pub fn players(&self, name: &str) -> Vec<String> {
+        let mut players = vec![name.to_string()];
+        if let ControllerChoice::Shared(friends) = self {
+            players.extend(friends.iter().cloned());
+        }
+        players
+    }
+
+    /// a fresh controller for a match, the keyboard can always quit
+    /// `shared`: the keys of each seat when sharing the keyboard
+    pub fn build(&self, keys: &KeyBindings, shared: &[KeyBindings]) -> Box<dyn Controller> {
+        match self {
+            ControllerChoice::Keyboard
+                => Box::new(KeyboardController::new(keys.clone())),
+            ControllerChoice::Bot(level)
+                => Box::new(Autopilot::new(Box::new(BotController::new(bot::new_bot(*level))), keys.clone())),
+            ControllerChoice::Script(_name, script)
+                => Box::new(Autopilot::new(Box::new(script.clone()), keys.clone())),
+            ControllerChoice::Shared(friends)
+                => Box::new(SharedKeyboard::new(friends.len() + 1, keys, shared)),
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! pub mod keys: which keys do what at the keyboard
+//! every action may have several keys, kept in the settings by name, such as `w`, `Left`, `Space` or `F3`
+//! letters are named in lower case and match with or without shift
+//! while typing a chat message, `Enter` sends it and `Esc` gives it up whatever the bindings
+
+use crate::controller::Input;
+use crate::yard::Direction;
+
+use crossterm::event::KeyCode;
+use serde::{ Deserialize, Serialize };
+
+use std::fmt;
+
+/// the presets known by name
+pub const PRESETS: [&str; 5] = ["classic", "vim", "wasd", "arrows", "ijkl"];
+/// the presets of players sharing a keyboard, by seat, the default of the settings
+/// only the first player's keys chat, show the diagnostics and quit
+pub const SHARED_LAYOUTS: [&str; 3] = ["wasd", "arrows", "ijkl"];
+/// the actions of each seat when sharing a keyboard
+pub const SHARED_ACTIONS: [&str; 5] = ["left", "right", "up", "down", "boost"];
+
+/// the name of a key as written in the settings, `None` for keys that can't be bound
+pub fn key_name(code: KeyCode) -> Option<String> {
+    match code {
+        KeyCode::Char(' ') => Some(String::from("Space")),
+        KeyCode::Char(c) => Some(c.to_lowercase().to_string()),
+        KeyCode::F(n) => Some(format!("F{}", n)),
+        KeyCode::Left => Some(String::from("Left")),
+        KeyCode::Right => Some(String::from("Right")),
+        KeyCode::Up => Some(String::from("Up")),
+        KeyCode::Down => Some(String::from("Down")),
+        KeyCode::Enter => Some(String::from("Enter")),
+        KeyCode::Tab => Some(String::from("Tab")),
+        KeyCode::Backspace => Some(String::from("Backspace")),
+        KeyCode::Esc => Some(String::from("Esc")),
+        _ => None,
+    }
+}
+
+/// the key of a name, regardless of case, `None` if there is no such key
+pub fn key_code(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_whitespace() && !c.is_control()
+            => { return c.to_lowercase().next().map(KeyCode::Char); },
+        _ => {},
+    }
+    match name.to_lowercase().as_str() {
+        "space" => Some(KeyCode::Char(' ')),
+        "left" => Some(KeyCode::Left),
+        "right" => Some(KeyCode::Right),
+        "up" => Some(KeyCode::Up),
+        "down" => Some(KeyCode::Down),
+        "enter" => Some(KeyCode::Enter),
+        "tab" => Some(KeyCode::Tab),
+        "backspace" => Some(KeyCode::Backspace),
+        "esc" => Some(KeyCode::Esc),
+        n => match n.strip_prefix('f').map(str::parse::<u8>) {
+            Some(Ok(f)) if (1..=12).contains(&f) => Some(KeyCode::F(f)),
+            _ => None,
+        },
+    }
+}
+
+/// reasons why key bindings can't be used
+#[derive(Debug, PartialEq)]
+pub enum KeyError {
+    /// a name that is not a key
+    Unknown(String),
+    /// a key bound to two actions
+    Conflict(String, String, String),
+    /// an action that must have a key has none
+    Unbound(String),
+    /// players sharing the keyboard have keys for this many seats, instead of as many as `SHARED_LAYOUTS`
+    Seats(usize),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyError::Unknown(k)
+                => write!(f, "there is no key \"{}\", try a letter, Space, Left, Right, Up, Down, Enter, Tab, Backspace, Esc or F1 to F12", k),
+            KeyError::Conflict(k, a, b)
+                => write!(f, "{} is bound to both {} and {}", k, a, b),
+            KeyError::Unbound(a)
+                => write!(f, "{} needs a key", a),
+            KeyError::Seats(n)
+                => write!(f, "there are keys for {} players sharing the keyboard, {} are expected", n, SHARED_LAYOUTS.len()),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+/// which keys do what, each action may have several keys
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    pub boost: Vec<String>,
+    /// begins a chat message
+    pub chat: Vec<String>,
+    /// shows or hides the network diagnostics
+    pub hud: Vec<String>,
+    pub quit: Vec<String>,
+}
+
+impl Default for KeyBindings {
+    /// `W A S D` or arrow keys to navigate, `Space` to boost, `T` to chat,
+    /// `F3` for the network diagnostics and `Esc` to quit
+    fn default() -> Self {
+        KeyBindings {
+            left: keys(&["Left", "a"]),
+            right: keys(&["Right", "d"]),
+            up: keys(&["Up", "w"]),
+            down: keys(&["Down", "s"]),
+            boost: keys(&["Space"]),
+            chat: keys(&["t"]),
+            hud: keys(&["F3"]),
+            quit: keys(&["Esc"]),
+        }
+    }
+}
+
+fn keys(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+/// every key of the actions is known and does one thing, and the turns and quitting have keys
+/// the actions are as in `KeyBindings::actions`, with the names they are told by
+fn check_bound<'a>(
+        actions: impl Iterator<Item = (&'static str, String, &'a Vec<String>)>,
+    ) -> Result<(), KeyError> {
+    let mut bound: Vec<(KeyCode, String)> = Vec::new();
+    for (kind, action, keys) in actions {
+        if keys.is_empty() && matches!(kind, "left" | "right" | "up" | "down" | "quit") {
+            return Err(KeyError::Unbound(action));
+        }
+        for k in keys {
+            let code = key_code(k).ok_or_else(|| KeyError::Unknown(k.clone()))?;
+            match bound.iter().find(|b| b.0 == code) {
+                Some((_code, other)) if *other != action
+                    => { return Err(KeyError::Conflict(k.clone(), other.clone(), action)); },
+                _ => { bound.push((code, action.clone())); },
+            }
+        }
+    }
+    Ok(())
+}
+
+/// the keys of players sharing the keyboard by default, a seat each
+pub fn shared_presets() -> Vec<KeyBindings> {
+    SHARED_LAYOUTS.iter().map(|l| KeyBindings::preset(l).unwrap()).collect()
+}
+
+/// whether players can share the keyboard with these keys, as `KeyBindings::check` for them all together
+///  - `keys`: the first player's, who chats, shows the diagnostics and quits for everyone
+///  - `seats`: each seat's keys to navigate and boost, the first player's first
+pub fn check_shared(keys: &KeyBindings, seats: &[KeyBindings]) -> Result<(), KeyError> {
+    if seats.len() != SHARED_LAYOUTS.len() {
+        return Err(KeyError::Seats(seats.len()));
+    }
+    let navigation = seats.iter().enumerate().flat_map(|(seat, b)| {
+        b.actions().into_iter()
+            .filter(|(action, _keys)| SHARED_ACTIONS.contains(action))
+            .map(move |(action, keys)| match seat {
+                0 => (action, action.to_string(), keys),
+                _ => (action, format!("{} for friend {}", action, seat), keys),
+            })
+    });
+    let common = [("chat", &keys.chat), ("hud", &keys.hud), ("quit", &keys.quit)];
+    check_bound(navigation.chain(common.into_iter().map(|(action, keys)| (action, action.to_string(), keys))))
+}
+
+impl KeyBindings {
+    /// the bindings known by name, see `PRESETS`
+    ///  - `classic`: the default ones
+    ///  - `vim`: `H J K L` or arrow keys to navigate, the rest as the default
+    ///  - `wasd`, `arrows` and `ijkl`: only these keys to navigate, for sharing the keyboard,
+    ///    boosting with `Space`, `Enter` and `O`
+    pub fn preset(name: &str) -> Option<KeyBindings> {
+        let navigate = |left, right, up, down, boost| KeyBindings {
+            left: keys(&[left]),
+            right: keys(&[right]),
+            up: keys(&[up]),
+            down: keys(&[down]),
+            boost: keys(&[boost]),
+            ..KeyBindings::default()
+        };
+        match name {
+            "classic" => Some(KeyBindings::default()),
+            "vim" => Some(KeyBindings {
+                left: keys(&["Left", "h"]),
+                right: keys(&["Right", "l"]),
+                up: keys(&["Up", "k"]),
+                down: keys(&["Down", "j"]),
+                ..KeyBindings::default()
+            }),
+            "wasd" => Some(navigate("a", "d", "w", "s", "Space")),
+            "arrows" => Some(navigate("Left", "Right", "Up", "Down", "Enter")),
+            "ijkl" => Some(navigate("j", "l", "i", "k", "o")),
+            _ => None,
+        }
+    }
+
+    /// the actions with their keys, in the order they are shown
+    pub fn actions(&self) -> [(&'static str, &Vec<String>); 8] {
+        [
+            ("left", &self.left),
+            ("right", &self.right),
+            ("up", &self.up),
+            ("down", &self.down),
+            ("boost", &self.boost),
+            ("chat", &self.chat),
+            ("hud", &self.hud),
+            ("quit", &self.quit),
+        ]
+    }
+
+    /// the keys of an action, as in `actions`
+    pub fn keys_mut(&mut self, action: &str) -> Option<&mut Vec<String>> {
+        match action {
+            "left" => Some(&mut self.left),
+            "right" => Some(&mut self.right),
+            "up" => Some(&mut self.up),
+            "down" => Some(&mut self.down),
+            "boost" => Some(&mut self.boost),
+            "chat" => Some(&mut self.chat),
+            "hud" => Some(&mut self.hud),
+            "quit" => Some(&mut self.quit),
+            _ => None,
+        }
+    }
+
+    /// whether the bindings can be played with: every key is known and does one thing,
+    /// and the snake can be turned every way and the game quit
+    pub fn check(&self) -> Result<(), KeyError> {
+        check_bound(self.actions().into_iter().map(|(action, keys)| (action, action.to_string(), keys)))
+    }
+
+    /// what the key is bound to
+    pub fn action(&self, code: KeyCode) -> Option<Input> {
+        let code = match code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_lowercase().next()?),
+            c => c,
+        };
+        let bound = |keys: &Vec<String>| keys.iter().any(|k| key_code(k) == Some(code));
+        if bound(&self.left) {
+            Some(Input::Turn(Direction::L))
+        } else if bound(&self.right) {
+            Some(Input::Turn(Direction::R))
+        } else if bound(&self.up) {
+            Some(Input::Turn(Direction::U))
+        } else if bound(&self.down) {
+            Some(Input::Turn(Direction::D))
+        } else if bound(&self.boost) {
+            Some(Input::Boost)
+        } else if bound(&self.chat) {
+            Some(Input::Typing(Some(String::new())))
+        } else if bound(&self.hud) {
+            Some(Input::ToggleHud)
+        } else if bound(&self.quit) {
+            Some(Input::Quit)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_go_both_ways() {
+        for code in [KeyCode::Char('w'), KeyCode::Char(' '), KeyCode::F(3), KeyCode::Left, KeyCode::Esc] {
+            assert_eq!(key_code(&key_name(code).unwrap()), Some(code));
+        }
+        assert_eq!(key_code("W"), Some(KeyCode::Char('w')));
+        assert_eq!(key_code("ENTER"), Some(KeyCode::Enter));
+        assert_eq!(key_code("F13"), None);
+        assert_eq!(key_code("Home"), None);
+        assert_eq!(key_name(KeyCode::Home), None);
+    }
+
+    #[test]
+    fn presets_can_be_played() {
+        for name in PRESETS {
+            assert_eq!(KeyBindings::preset(name).unwrap().check(), Ok(()));
+        }
+        assert_eq!(KeyBindings::preset("emacs"), None);
+        assert_eq!(check_shared(&KeyBindings::default(), &shared_presets()), Ok(()));
+    }
+
+    #[test]
+    fn check_finds_conflicts() {
+        let mut keys = KeyBindings::default();
+        keys.boost.push(String::from("W"));
+        assert_eq!(
+            keys.check(),
+            Err(KeyError::Conflict(String::from("W"), String::from("up"), String::from("boost"))),
+        );
+        let mut keys = KeyBindings::default();
+        keys.left.clear();
+        assert_eq!(keys.check(), Err(KeyError::Unbound(String::from("left"))));
+        keys.left.push(String::from("Home"));
+        assert_eq!(keys.check(), Err(KeyError::Unknown(String::from("Home"))));
+        // boosting may go without a key
+        let keys = KeyBindings { boost: Vec::new(), ..KeyBindings::default() };
+        assert_eq!(keys.check(), Ok(()));
+    }
+
+    #[test]
+    fn check_shared_finds_conflicts_between_seats() {
+        let keys = KeyBindings::default();
+        assert_eq!(check_shared(&keys, &shared_presets()[..2]), Err(KeyError::Seats(2)));
+        let mut seats = shared_presets();
+        seats[2].up = vec![String::from("w")];
+        assert_eq!(
+            check_shared(&keys, &seats),
+            Err(KeyError::Conflict(String::from("w"), String::from("up"), String::from("up for friend 2"))),
+        );
+        let mut seats = shared_presets();
+        seats[1].boost = vec![String::from("t")];
+        assert_eq!(
+            check_shared(&keys, &seats),
+            Err(KeyError::Conflict(String::from("t"), String::from("boost for friend 1"), String::from("chat"))),
+        );
+    }
+
+    #[test]
+    fn action_ignores_case() {
+        let keys = KeyBindings::default();
+        assert_eq!(keys.action(KeyCode::Char('A')), Some(Input::Turn(Direction::L)));
+        assert_eq!(keys.action(KeyCode::F(3)), Some(Input::ToggleHud));
+        assert_eq!(keys.action(KeyCode::Char('z')), None);
+    }
+}
@@ -0,0 +1,92 @@
+//! # socket_snake
+//! This is firstly a software laboratory for the XJTU course computer network, then implemented multiplayer snake game, with Rust.
+//! Main contibutors:
+//!  - [James-Hen](https://github.com/James-Hen)
+//!  - [yanxwb](https://github.com/yanxwb)
+//!
+//! Besides the game itself, the crate is a library for tools around it, such as bots, dashboards and test harnesses:
+//!  - the simulation: [`YardSim`] plays the rules tick by tick, and [`YardState`] is a snapshot of it
+//!  - the protocol: [`YardCtrl`] goes to the server and [`YardInfo`] comes back, sent by [`transmit`]
+//!  - the rendering helpers: [`TUIHelper`] draws the yard and boards in the terminal
+//!
+//! The types re-exported here are the stable API, the modules hold more details.
+//!
+//! ```
+//! use socket_snake::{ GameConfig, YardSim, Direction };
+//!
+//! let mut y = YardSim::with_seed(&GameConfig::default(), 42);
+//! let me = y.init_snake().unwrap();
+//! y.control_snake(me, Direction::U);
+//! let (score, _failed) = y.next_tick();
+//! assert_eq!(score[me as usize], GameConfig::default().init_snake_len);
+//! assert!(y.snapshot().snake(me).is_some());
+//! ```
+
+// the modules of the first version predate some of the lints, they are allowed there alone
+/// Snake game rule implementation, simulate step by step in ticks
+#[allow(
+    unused_doc_comments,
+    clippy::clone_on_copy,
+    clippy::empty_line_after_doc_comments,
+    clippy::partialeq_to_none,
+)]
+pub mod yard;
+/// Obstacle maps, their text format and the built-in ones
+pub mod map;
+/// Helper module that defined some ui rendering components and utilities
+#[allow(clippy::empty_line_after_doc_comments)]
+pub mod render;
+/// What players may be called
+pub mod names;
+/// Computer controlled players
+pub mod bot;
+/// Headless bot-vs-bot games, for evaluating bots
+pub mod tournament;
+/// Where the directions of a snake come from: keyboard, bots and scripts
+pub mod controller;
+/// Which keys do what, and presets of them
+pub mod keys;
+/// The player's profile and preferences, kept between launches
+pub mod settings;
+/// Ticking at a steady rate
+pub mod scheduler;
+/// Guessing the own snake's turns before the server confirms them
+pub mod predict;
+/// The host's leaderboard and match history, kept between games
+pub mod records;
+/// Game simulation thread implementation
+#[allow(clippy::empty_line_after_doc_comments)]
+pub mod server;
+/// User interface, game control threads implementation
+#[doc(hidden)]
+#[allow(clippy::empty_line_after_doc_comments)]
+pub mod client;
+/// Defines the user interaction that improves user's experience
+#[doc(hidden)]
+#[allow(clippy::empty_line_after_doc_comments, clippy::io_other_error, clippy::single_match)]
+pub mod snakeux;
+/// How clients and the server are connected, through channels or sockets
+pub mod transport;
+/// Bad network conditions on purpose, for testing on a single machine
+pub mod netsim;
+/// Server and client wrappers to introduce sockets and channels
+#[allow(clippy::empty_line_after_doc_comments)]
+pub mod multiplayer;
+/// Helper module that defined some network transmitting components and utilities
+#[macro_use]
+#[allow(clippy::empty_line_after_doc_comments, clippy::unused_io_amount)]
+pub mod transmit;
+
+// the simulation
+pub use yard::{
+    YardSim, YardState, SnakeState, GameConfig, ConfigError, MatchMode, BotLevel, Coord, Direction, PowerUp, Effects, MAX_PLAYERS,
+};
+pub use map::{ YardMap, MapError };
+pub use bot::{ Bot, new_bot, new_seeded_bot };
+pub use controller::{ Controller, Input };
+pub use keys::KeyBindings;
+// the protocol
+pub use server::{ YardCtrl, YardInfo, BoardType, start_and_serve };
+pub use transport::{ ClientTransport, ServerTransport, Delivery };
+// the rendering helpers
+pub use render::{ Frontend, TUIHelper, HeadlessFrontend, TUIBlock, YardBuf, AsciicastRecorder, Theme };
@@ -1,38 +1,112 @@
 //! # socket_snake
-//! This is firstly a software laboratory for the XJTU course computer network, then implemented multiplayer snake game, with Rust.
-//! Main contibutors:
-//!  - [James-Hen](https://github.com/James-Hen)
-//!  - [yanxwb](https://github.com/yanxwb)
+//! The game binary, with the menu and the `tournament` command, see the library for the rest.
+//! `--netsim <conditions>` plays under simulated bad network conditions, see `netsim`.
+//! The name and preferences are kept in the settings file, see `settings`.
 
-/// Snake game rule implementation, simulate step by step in ticks
-pub mod yard;
-/// Helper module that defined some ui rendering components and utilities
-pub mod render;
-/// Game simulation thread implementation
-pub mod server;
-/// User interface, game control threads implementation
-pub mod client;
-/// Defines the user interaction that improves user's experience
-pub mod snakeux;
-/// Server and client wrappers to introduce sockets and channels
-pub mod multiplayer;
-/// Helper module that defined some network transmitting components and utilities
-#[macro_use]
-pub mod transmit;
+use socket_snake::{ snakeux, multiplayer, tournament, GameConfig };
+use socket_snake::controller::ControllerChoice;
+use socket_snake::netsim::NetConditions;
+use socket_snake::settings::Settings;
+use socket_snake::keys::{ self, KeyBindings };
+
+/// how `--netsim` is given, told when it is not understood
+const NETSIM_USAGE: &str = "usage: socket_snake [--netsim <conditions>], such as --netsim wifi,loss=10";
+
+/// write the settings down unless they can't be, a failure is told but the game goes on
+fn save_settings(settings: &Settings, savable: bool) {
+    if !savable {
+        return;
+    }
+    if let Err(e) = settings.save() {
+        eprintln!("Settings not saved, {}", e);
+    }
+}
 
 fn main() {
-    let mut name = snakeux::random_name(); // will be used next time, be sure to clone
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("tournament") {
+        if let Err(e) = tournament::run_cli(&args[2..]) {
+            eprintln!("{}\n{}", e, tournament::USAGE);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let netsim = match args.iter().position(|a| a == "--netsim") {
+        // without conditions the link would be perfect, which is surely not what was meant
+        Some(i) => match args.get(i + 1).map(String::as_str) {
+            Some(spec) if !spec.trim().is_empty() => match NetConditions::parse(spec) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    eprintln!("{}\n{}", e, NETSIM_USAGE);
+                    std::process::exit(1);
+                },
+            },
+            _ => {
+                eprintln!("--netsim needs the conditions\n{}", NETSIM_USAGE);
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+    // a file that can't be loaded is kept, and if it can't be moved aside nothing is saved over it
+    let (mut settings, savable) = match Settings::load() {
+        Ok(s) => (s, true),
+        Err(e) => match Settings::back_up() {
+            Ok(backup) => {
+                eprintln!("{}, playing with the default settings, the file is kept as {}", e, backup.display());
+                (Settings::default(), true)
+            },
+            Err(_e) => {
+                eprintln!("{}, playing with the default settings, which won't be saved", e);
+                (Settings::default(), false)
+            },
+        },
+    };
+    if let Err(e) = settings.keys.check() {
+        eprintln!("Key bindings not usable, {}, playing with the default keys", e);
+        settings.keys = KeyBindings::default();
+    }
+    if let Err(e) = keys::check_shared(&settings.keys, &settings.shared_keys) {
+        eprintln!("Keys for sharing the keyboard not usable, {}, sharing it with the usual ones", e);
+        settings.shared_keys = keys::shared_presets();
+    }
+    if let Err(e) = settings.game.check() {
+        eprintln!("Usual game settings not usable, {}, playing with the default ones", e);
+        settings.game = GameConfig::default();
+    }
+    if settings.name.is_empty() {
+        settings.name = snakeux::random_name();
+        save_settings(&settings, savable);
+    }
+    let mut recording = false;
+    let mut controller = ControllerChoice::Keyboard;
     loop {
-        let choice = snakeux::show_main_menu(&mut name).unwrap();
+        let choice = snakeux::show_main_menu(&mut settings, recording, &controller).unwrap();
+        let record = if recording { Some(snakeux::record_path()) } else { None };
+        let players = controller.players(&settings.name);
         match choice {
-            snakeux::UsersIdea::Singleplayer
-                => { multiplayer::singleplayer_start(name.clone()); },
-            snakeux::UsersIdea::JoinGame(addr)
-                => { multiplayer::client_start(name.clone(), addr); },
-            snakeux::UsersIdea::HostGame(addr)
-                => { multiplayer::server_start(addr).unwrap(); },
-            snakeux::UsersIdea::ChangeName
+            snakeux::UsersIdea::Singleplayer(config) => {
+                save_settings(&settings, savable);
+                multiplayer::singleplayer_start(
+                    players, config, controller.build(&settings.keys, &settings.shared_keys), record, netsim, settings.theme);
+            },
+            snakeux::UsersIdea::JoinGame(addr, team) => {
+                save_settings(&settings, savable);
+                multiplayer::client_start(
+                    players, team, addr, controller.build(&settings.keys, &settings.shared_keys), record, netsim, settings.theme);
+            },
+            snakeux::UsersIdea::HostGame(addr, config, delivery) => {
+                save_settings(&settings, savable);
+                multiplayer::server_start(addr, config, delivery).unwrap();
+            },
+            snakeux::UsersIdea::ToggleRecording
+                => { recording = !recording; },
+            snakeux::UsersIdea::ChangeName | snakeux::UsersIdea::ChangeSettings
+                => { save_settings(&settings, savable); },
+            snakeux::UsersIdea::ShowLeaderboard
                 => {},
+            snakeux::UsersIdea::ChangeController(c)
+                => { controller = c; },
             snakeux::UsersIdea::ExitGame
                 => { break; }
         }
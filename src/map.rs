@@ -0,0 +1,317 @@
+//! pub mod map: obstacle maps that the yard can load
+//! a map file is plain text, for example:
+//! ```text
+//! # lines beginning with '#' before the grid are comments
+//! name: Pillars
+//! ..........
+//! ..##..##..
+//! ..S....*..
+//! ```
+//!  - `.` is an empty block, `#` a wall
+//!  - `S` a spawn point, snakes are born with their heads on spawn points
+//!  - `*` a bean spawn zone, if there is any, beans only grow inside zones
+//!
+//! every grid row shall have the same width, the fence is added outside
+
+use crate::yard::{ Coord, GameConfig, MAX_PLAYERS };
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+pub const MIN_MAP_SIZE: usize = 5;
+pub const MAX_MAP_WIDTH: usize = 60;
+pub const MAX_MAP_HEIGHT: usize = 40;
+
+pub const TILE_EMPTY: char = '.';
+pub const TILE_WALL: char = '#';
+pub const TILE_SPAWN: char = 'S';
+pub const TILE_BEAN_ZONE: char = '*';
+
+/// the maps shipped with the game, besides the open field
+pub const BUILTIN_MAPS: [&str; 3] = [
+    r#"name: Pillars
+..............................
+..............................
+..............................
+....##....##......##....##....
+....##....##......##....##....
+..............................
+.......S..............S.......
+..............................
+..............................
+.............****.............
+.............****.............
+..............................
+..............................
+.......S..............S.......
+..............................
+....##....##......##....##....
+....##....##......##....##....
+..............................
+..............................
+..............................
+"#,
+    r#"name: Cross
+..............................
+..............................
+..............#...............
+...S..........#..........S....
+..............#...............
+..............#...............
+..............#...............
+..............................
+..............................
+....#######..****..#######....
+....#######..****..#######....
+..............................
+..............................
+..............#...............
+..............#...............
+..............#...............
+...S..........#..........S....
+..............#...............
+..............................
+..............................
+"#,
+    r#"name: Rooms
+..............#...............
+..............#...............
+...S..........#.........S.....
+..............................
+.......*......#.......*.......
+..............#...............
+..............#...............
+..............#...............
+..............#...............
+#####...#######.#######...####
+..............#...............
+..............#...............
+..............#...............
+..............#...............
+.......*......#.......*.......
+..............................
+...S..........#.........S.....
+..............#...............
+..............#...............
+..............#...............
+"#,
+];
+
+/// reasons why a map file can't be loaded
+#[derive(Debug)]
+pub enum MapError {
+    Io(std::io::Error),
+    /// there is no grid row at all
+    Empty,
+    /// a header line that is not understood, with the line number
+    BadHeader(usize, String),
+    /// line number, expected width, found width
+    RaggedRow(usize, usize, usize),
+    /// line number, column number, the character
+    UnknownTile(usize, usize, char),
+    /// width and height of the grid
+    BadSize(usize, usize),
+    /// a wall, spawn point or bean zone outside of the grid
+    OffMap(Coord),
+    /// a block given twice, as a wall, spawn point or bean zone
+    Overlap(Coord),
+    /// free blocks, and blocks needed for the beans and every player's snake
+    NoRoom(usize, usize),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Io(e)
+                => write!(f, "can't read the map file: {}", e),
+            MapError::Empty
+                => write!(f, "the map has no grid rows"),
+            MapError::BadHeader(line, h)
+                => write!(f, "line {}: unknown header \"{}\"", line, h),
+            MapError::RaggedRow(line, expected, found)
+                => write!(f, "line {}: row is {} blocks wide, expected {}", line, found, expected),
+            MapError::UnknownTile(line, col, ch)
+                => write!(f, "line {}, column {}: unknown tile '{}'", line, col, ch),
+            MapError::BadSize(w, h)
+                => write!(
+                    f, "the map is {}x{}, it shall be between {}x{} and {}x{}",
+                    w, h, MIN_MAP_SIZE, MIN_MAP_SIZE, MAX_MAP_WIDTH, MAX_MAP_HEIGHT,
+                ),
+            MapError::OffMap(c)
+                => write!(f, "row {}, column {} is off the map", c.0 + 1, c.1 + 1),
+            MapError::Overlap(c)
+                => write!(f, "row {}, column {} is given twice", c.0 + 1, c.1 + 1),
+            MapError::NoRoom(free, needed)
+                => write!(f, "the map has {} free blocks, the beans and snakes need {}", free, needed),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+/// a parsed and validated map, small enough to be sent along with the game config
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct YardMap {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub walls: Vec<Coord>,
+    pub spawns: Vec<Coord>,
+    pub bean_zone: Vec<Coord>,
+}
+
+impl YardMap {
+    /// parse the text format, see the module document
+    pub fn parse(source: &str) -> Result<YardMap, MapError> {
+        let mut map = YardMap {
+            name: String::from("Unnamed"),
+            width: 0,
+            height: 0,
+            walls: Vec::new(),
+            spawns: Vec::new(),
+            bean_zone: Vec::new(),
+        };
+        for (i, line) in source.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim_end();
+            if line.is_empty() || map.height == 0 && !is_grid_row(line) && line.starts_with('#') {
+                continue; // comments, a row of the grid can't be one
+            }
+            if !is_grid_row(line) && map.height == 0 {
+                match line.split_once(':') {
+                    Some((key, value)) if key.trim() == "name" => {
+                        map.name = value.trim().to_string();
+                        continue;
+                    },
+                    _ => { return Err(MapError::BadHeader(line_no, line.to_string())); },
+                }
+            }
+            // a grid row
+            let row = map.height;
+            let mut width = 0;
+            for (col, ch) in line.chars().enumerate() {
+                match ch {
+                    TILE_EMPTY => {},
+                    TILE_WALL => { map.walls.push(Coord(row, col)); },
+                    TILE_SPAWN => { map.spawns.push(Coord(row, col)); },
+                    TILE_BEAN_ZONE => { map.bean_zone.push(Coord(row, col)); },
+                    _ => { return Err(MapError::UnknownTile(line_no, col + 1, ch)); },
+                }
+                width += 1;
+            }
+            if map.height == 0 {
+                map.width = width;
+            } else if width != map.width {
+                return Err(MapError::RaggedRow(line_no, map.width, width));
+            }
+            map.height += 1;
+        }
+        if map.height == 0 {
+            return Err(MapError::Empty);
+        }
+        map.check()?;
+        Ok(map)
+    }
+
+    /// whether the map can be played, for maps that were not parsed, such as in the settings
+    /// the usual game shall fit, the one played is checked again with its own config
+    pub fn check(&self) -> Result<(), MapError> {
+        if !(MIN_MAP_SIZE..=MAX_MAP_WIDTH).contains(&self.width)
+            || !(MIN_MAP_SIZE..=MAX_MAP_HEIGHT).contains(&self.height) {
+            return Err(MapError::BadSize(self.width, self.height));
+        }
+        let blocks = self.walls.iter().chain(&self.spawns).chain(&self.bean_zone);
+        if let Some(c) = blocks.clone().find(|c| c.0 >= self.height || c.1 >= self.width) {
+            return Err(MapError::OffMap(*c));
+        }
+        // each block once, so the free ones can be counted
+        let mut seen = HashSet::new();
+        if let Some(c) = blocks.clone().find(|c| !seen.insert(**c)) {
+            return Err(MapError::Overlap(*c));
+        }
+        let usual = GameConfig::default();
+        self.check_room(usual.bean_count, usual.init_snake_len)
+    }
+
+    /// blocks that are not walls
+    pub fn free_blocks(&self) -> usize {
+        (self.width * self.height).saturating_sub(self.walls.len())
+    }
+
+    /// whether there is room for the beans and a snake of every player
+    pub fn check_room(&self, bean_count: usize, init_snake_len: usize) -> Result<(), MapError> {
+        let needed = bean_count.saturating_add(init_snake_len.saturating_mul(MAX_PLAYERS as usize));
+        match self.free_blocks() {
+            free if free < needed => Err(MapError::NoRoom(free, needed)),
+            _ => Ok(()),
+        }
+    }
+
+    /// read and parse a map file
+    pub fn load(path: &str) -> Result<YardMap, MapError> {
+        YardMap::parse(&fs::read_to_string(path).map_err(MapError::Io)?)
+    }
+
+    /// all the built-in maps, they are valid for sure
+    pub fn builtins() -> Vec<YardMap> {
+        BUILTIN_MAPS.iter()
+            .map(|source| YardMap::parse(source).unwrap())
+            .collect()
+    }
+}
+
+/// whether the line only consists of tiles
+fn is_grid_row(line: &str) -> bool {
+    line.chars().all(|ch| matches!(ch, TILE_EMPTY | TILE_WALL | TILE_SPAWN | TILE_BEAN_ZONE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtins_parse() {
+        let maps = YardMap::builtins();
+        assert_eq!(maps.len(), BUILTIN_MAPS.len());
+        for map in maps {
+            assert_eq!((map.width, map.height), (30, 20));
+            assert_eq!(map.spawns.len(), 4);
+        }
+    }
+
+    #[test]
+    fn parse_reads_the_tiles() {
+        let map = YardMap::parse("name: Box\n# a comment\n#....\n..S..\n..*..\n.....\n....#\n").unwrap();
+        assert_eq!(map.name, "Box");
+        assert_eq!((map.width, map.height), (5, 5));
+        assert_eq!(map.walls, vec![Coord(0, 0), Coord(4, 4)]);
+        assert_eq!(map.spawns, vec![Coord(1, 2)]);
+        assert_eq!(map.bean_zone, vec![Coord(2, 2)]);
+    }
+
+    #[test]
+    fn parse_tells_what_is_wrong() {
+        assert!(matches!(YardMap::parse("name: None\n"), Err(MapError::Empty)));
+        assert!(matches!(YardMap::parse("size: 5\n....."), Err(MapError::BadHeader(1, _))));
+        assert!(matches!(YardMap::parse(".....\n....\n"), Err(MapError::RaggedRow(2, 5, 4))));
+        assert!(matches!(YardMap::parse(".....\n..x..\n"), Err(MapError::UnknownTile(2, 3, 'x'))));
+        assert!(matches!(YardMap::parse(".....\n#...x\n"), Err(MapError::UnknownTile(2, 5, 'x'))));
+        assert!(matches!(YardMap::parse(".....\n# note\n"), Err(MapError::UnknownTile(2, 2, ' '))));
+        assert!(matches!(YardMap::parse("....\n....\n....\n....\n"), Err(MapError::BadSize(4, 4))));
+        let walled = format!("{}\n", "#####\n".repeat(5));
+        assert!(matches!(YardMap::parse(&walled), Err(MapError::NoRoom(0, _))));
+    }
+
+    #[test]
+    fn check_finds_blocks_off_the_map_or_twice() {
+        let mut map = YardMap::builtins().remove(0);
+        map.walls.push(Coord(0, map.width));
+        assert!(matches!(map.check(), Err(MapError::OffMap(Coord(0, 30)))));
+        map.walls.pop();
+        map.bean_zone.push(map.walls[0]);
+        assert!(matches!(map.check(), Err(MapError::Overlap(_))));
+    }
+}
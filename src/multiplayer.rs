@@ -3,195 +3,190 @@
 
 use crate::{ server, client };
 use crate::server::{ YardCtrl, YardInfo };
+use crate::yard::GameConfig;
 use crate::transmit::*;
-use crate::{ tcp_recv, udp_recv };
+use crate::controller::Controller;
+use crate::render::{ Frontend, TUIHelper, Theme };
+use crate::transport::{ self, ClientTransport, ServerTransport, NetServer, NetClient, Delivery, Claims };
+use crate::netsim::{ NetConditions, SimClient };
+use crate::records;
 
 use std::thread;
-use std::sync::mpsc::{ self, TryRecvError };
-use std::net::{ Shutdown, TcpListener, TcpStream, UdpSocket, Ipv4Addr };
-use std::time::Duration;
+use std::io::BufReader;
+use std::sync::{ Arc, Mutex, mpsc };
+use std::net::{ Shutdown, TcpListener, TcpStream };
+
+pub use crate::transport::{ UDP_SERVER_PORT, UDP_CLIENT_PORT, MULTICAST_GROUP_PORT, MULTICAST_GROUP_ADDR };
 
 pub const TCP_SERVER_PORT: &str = ":14514";
-pub const UDP_SERVER_PORT: &str = "0.0.0.0:19198";
-pub const UDP_CLIENT_PORT: &str = "0.0.0.0:10114";
-// multicast group can be from 234.0.2.0 to 238.255.255.255
-pub const MULTICAST_GROUP_PORT: &str = "234.51.4.19:10114";
-pub const MULTICAST_GROUP_ADDR: &Ipv4Addr = &Ipv4Addr::new(234, 51, 4, 19);
-
-pub fn singleplayer_start(name: String) {
-    // server sends to clients
-    let (info_tx, info_rx) = mpsc::channel();
-    // client sends to servers
-    let (ctrl_tx, ctrl_rx) = mpsc::channel();
+/// programmatic players speak line-based JSON here, see `handle_bot_connection`
+pub const BOT_SERVER_PORT: &str = ":14515";
+
+/// the terminal in the theme, recording it if asked to
+fn terminal_frontend(record: &Option<String>, theme: Theme) -> Box<dyn Frontend> {
+    match record {
+        Some(_) => Box::new(TUIHelper::with_recorder().with_theme(theme)),
+        None => Box::new(TUIHelper::new().with_theme(theme)),
+    }
+}
+
+/// serve the game, recording it on this computer if there is somewhere to keep the records
+fn serve_recorded(config: GameConfig, transport: &dyn ServerTransport) {
+    match records::records_path() {
+        Some(path) => server::start_and_serve_recorded(config, transport, &path),
+        None => server::start_and_serve(config, transport),
+    }
+}
+
+/// the connection as it is, or under simulated conditions if asked to
+fn under_conditions(transport: Arc<dyn ClientTransport>, netsim: Option<NetConditions>) -> Arc<dyn ClientTransport> {
+    match netsim {
+        Some(c) => Arc::new(SimClient::wrap(transport, c)),
+        None => transport,
+    }
+}
+
+/// play alone, or with friends at the same keyboard, a snake for each of the `names`
+pub fn singleplayer_start(
+        names: Vec<String>,
+        config: GameConfig,
+        controller: Box<dyn Controller>,
+        record: Option<String>,
+        netsim: Option<NetConditions>,
+        theme: Theme,
+    ) {
+    let (client_end, server_end) = transport::channel_pair();
 
     let server_handle = thread::spawn(move || {
-        server::start_and_serve(info_tx, ctrl_rx);
+        serve_recorded(config, &server_end);
     });
 
     let client_handle = thread::spawn(move || {
-        client::start_and_play(name, info_rx, ctrl_tx);
+        let ui = terminal_frontend(&record, theme);
+        let transport = under_conditions(Arc::new(client_end), netsim);
+        client::start_and_play(names, None, transport, controller, ui, record);
     });
 
     server_handle.join().unwrap_or(()); // Ok to SendError, client exits
     client_handle.join().unwrap();      // Not Ok to quit badly
 }
 
-pub fn handle_connection(ctrl_tx: mpsc::Sender<YardCtrl>, mut stream: TcpStream) {
-    println!("Connected one client, establishing UDP connection");
-    // send the multicast group for the client to join
-    tcp_send(&mut stream, MULTICAST_GROUP_ADDR).unwrap();
-    println!("UDP Multicast address sent");
+/// serve a programmatic player with the line-based JSON protocol:
+///  - the bot sends `YardCtrl`s, such as `{"NewSnake":[42,"my bot",null]}` and `{"CtrlSnake":[42,"L"]}`
+///  - the server sends every `YardInfo` but screen buffers, such as `{"Spawned":[42,1]}`,
+///    `{"State":{...}}` each tick, and `{"Failed":42}`
+///
+/// a bot only controls the snakes it registered, see `Claims`, and its pings are answered to it alone
+///
+/// see the README for the whole protocol
+pub fn handle_bot_connection(
+        ctrl_tx: mpsc::Sender<YardCtrl>,
+        stream: TcpStream,
+        info_rx: mpsc::Receiver<YardInfo>,
+        claims: Claims,
+    ) {
+    println!("Connected one bot");
+    // the infos and the answers to pings take turns, a line at a time
+    let write_stream = Arc::new(Mutex::new(stream.try_clone().expect("clone TCP stream failed")));
+    let info_stream = Arc::clone(&write_stream);
+    thread::spawn(move || {
+        // ends when the bot is gone
+        while let Ok(info) = info_rx.recv() {
+            if matches!(info, YardInfo::RefreshScreen(_)) {
+                continue; // drawing is up to the bot
+            }
+            if json_send(&mut *info_stream.lock().unwrap(), &info).is_err() {
+                break;
+            }
+        }
+    });
     thread::spawn(move || {
+        let mut reader = BufReader::new(stream.try_clone().expect("clone TCP stream failed"));
+        let conn = claims.connect();
         loop {
-            let op: YardCtrl = match tcp_recv!(stream) {
+            let op: YardCtrl = match json_recv(&mut reader) {
                 Ok(YardCtrl::QuitGame) => {
-                    println!("Quit game intercepted, releasing connection");
+                    println!("Bot quitted, releasing connection");
                     break;
                 },
                 Ok(c) => { c },
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    println!("Bot sent an invalid line {}", e);
+                    continue;
+                },
                 Err(e) => {
                     println!("Receiving failed {}", e);
                     break;
                 },
             };
-            match ctrl_tx.send(op.clone()) {
-                Ok(_) => {
-                    println!("Request handled: {:?}", op);
+            match op {
+                YardCtrl::Ping(n) => {
+                    json_send(&mut *write_stream.lock().unwrap(), &YardInfo::Pong(n)).unwrap_or(());
+                    continue;
                 },
-                Err(_) => {
-                    println!("Server quitted, ending TCP connection");
-                    break;
-                }
-            };
-        }
-        stream.shutdown(Shutdown::Both).expect("shutdown call failed");
-    });
-}
-
-pub fn server_start(server_addr: String) -> std::io::Result<()> {
-    // server sends to clients
-    let (info_tx, info_rx) = mpsc::channel();
-    // client sends to servers
-    let (ctrl_tx, ctrl_rx) = mpsc::channel();
-    // start the backend
-    let _server_handle = thread::spawn(move || {
-        server::start_and_serve(info_tx, ctrl_rx);
-    });
-
-    // info from server (info_rx) always sent to UDP multicast
-    let socket = UdpSocket::bind(UDP_SERVER_PORT).unwrap();
-    socket.join_multicast_v4(&MULTICAST_GROUP_ADDR, &Ipv4Addr::UNSPECIFIED)
-        .expect("Couldn't join multicast");
-    socket.set_multicast_loop_v4(false).expect("set_multicast_loop_v4 call failed");
-    thread::spawn(move || {
-        loop {
-            let info = match info_rx.recv() {
-                Ok(c) => c,
-                Err(_e) => break, // client quitted
-            };
-            udp_send(&socket, MULTICAST_GROUP_PORT, &info).unwrap();
-            // printing information
-            match info {
-                YardInfo::RegisteredSnake(_, _) | YardInfo::Failed(_) => {
-                    println!("Sent signal {:?}", info);
+                _ if !claims.admit(conn, &op) => {
+                    println!("Bot sent a ctrl of someone else's snake {:?}", op);
+                    continue;
                 },
                 _ => {},
             }
-        }
-    });
-
-    // communicate through TCP
-    let listener = TcpListener::bind(&server_addr)?;
-    println!("Listening");
-    // server wrapper listens for connection and send through channel
-    loop {
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    stream.set_read_timeout(Some(Duration::from_secs(100)))
-                        .expect("set_read_timeout call failed");
-                    // spawn a child that sends ctrl to the backend
-                    let ctrl_tx_clone = mpsc::Sender::clone(&ctrl_tx);
-                    handle_connection(ctrl_tx_clone, stream);
-                },
-                Err(_e) => {},
+            if ctrl_tx.send(op).is_err() {
+                println!("Server quitted, ending bot connection");
+                break;
             }
         }
-    }
-}
-
-pub fn client_start(name: String, server_addr: String) {
-    // server sends to clients
-    let (info_tx, info_rx) = mpsc::channel();
-    // client sends to servers
-    let (ctrl_tx, ctrl_rx) = mpsc::channel();
-
-    // sending ctrl signal using TCP
-    println!("Connecting to {} ...", &server_addr);
-    let mut stream = loop {
-        match TcpStream::connect(&server_addr) {
-            Ok(s) => { break s; },
-            Err(e) => { println!("{}, retrying ...", e); },
-        };
-    };
-
-    // when TCP connected, use TCP to receive a multicast group
-    let group_addr: Ipv4Addr = tcp_recv!(stream).unwrap();
-    // start listening UDP for buffer and information
-    let socket = UdpSocket::bind(UDP_CLIENT_PORT).unwrap();
-    socket.join_multicast_v4(&group_addr, &Ipv4Addr::UNSPECIFIED)
-        .expect("Couldn't join multicast");
-
-    println!("Multicast set up, firing up game!");
-
-    // UDP listening thread, use channel to notify an end of service
-    let (listener_kill, listener_killed) = mpsc::channel::<bool>();
-    let listening_handle = thread::spawn(move || {
-        loop {
-            // child control
-            match listener_killed.try_recv() {
-                Ok(_) => { return; },
-                Err(TryRecvError::Empty) => {},
-                Err(TryRecvError::Disconnected) => { return; },
-            }
-            // doing work
-            let server_info: YardInfo = match udp_recv!(&socket) {
-                Ok(i) => { i }, // be silent to users
-                Err(_) => { break; }, // client quitted
-            };
-            match info_tx.send(server_info) {
-                Ok(_) => {}, // be silent to users
-                Err(_) => { break; }, // client quitted
-            };
+        for rid in claims.disconnect(conn) {
+            ctrl_tx.send(YardCtrl::LeaveGame(rid)).unwrap_or(());
         }
+        stream.shutdown(Shutdown::Both).unwrap_or(());
     });
+}
 
-    // TCP sending thread
-    let (sender_kill, sender_killed) = mpsc::channel::<bool>();
-    let sending_handle = thread::spawn(move || {
-        loop {
-            // child control
-            match sender_killed.try_recv() {
-                Ok(_) => { break; },
-                Err(TryRecvError::Empty) => {},
-                Err(TryRecvError::Disconnected) => { break; },
-            }
-            // doing work
-            let ctrl = match ctrl_rx.recv() {
-                Ok(c) => c,
-                Err(_e) => break, // client quitted
-            };
-            tcp_send(&mut stream, &ctrl).unwrap();
+/// serve the game on `server_addr`, infos delivered the given way
+pub fn server_start(server_addr: String, config: GameConfig, delivery: Delivery) -> std::io::Result<()> {
+    let transport = Arc::new(NetServer::bind(&server_addr, delivery)?);
+
+    // bots speak JSON on another port of the same host
+    let bot_addr = match server_addr.rsplit_once(':') {
+        Some((ip, _port)) => format!("{}{}", ip, BOT_SERVER_PORT),
+        None => format!("{}{}", server_addr, BOT_SERVER_PORT),
+    };
+    let bot_listener = TcpListener::bind(&bot_addr)?;
+    let bot_transport = Arc::clone(&transport);
+    println!("Listening bots on {}", bot_addr);
+    thread::spawn(move || {
+        for stream in bot_listener.incoming().flatten() {
+            handle_bot_connection(
+                bot_transport.ctrl_sender(), stream, bot_transport.subscribe(), bot_transport.claims(),
+            );
         }
-        // shutdown TCP connection
-        stream.shutdown(Shutdown::Both).expect("Shutdown TCP connection failed");
     });
 
-    client::start_and_play(name, info_rx, ctrl_tx); // note: will not return till end
+    // the backend runs till the server quits
+    serve_recorded(config, transport.as_ref());
+    transport.close();
+    Ok(())
+}
 
-    // if user ended playing, clean up the threads by just dropping the channel
-    drop(listener_kill);
-    drop(sender_kill);
-    listening_handle.join().unwrap();
-    sending_handle.join().unwrap();
-}
\ No newline at end of file
+/// join the game on `server_addr`, a snake for each of the `names`
+pub fn client_start(
+        names: Vec<String>,
+        team: Option<u8>,
+        server_addr: String,
+        controller: Box<dyn Controller>,
+        record: Option<String>,
+        netsim: Option<NetConditions>,
+        theme: Theme,
+    ) {
+    let transport = match NetClient::connect(&server_addr) {
+        Ok(t) => under_conditions(Arc::new(t), netsim),
+        Err(e) => {
+            println!("Failed to connect {}", e);
+            return;
+        },
+    };
+    // note: will not return till end
+    let ui = terminal_frontend(&record, theme);
+    client::start_and_play(names, team, Arc::clone(&transport), controller, ui, record);
+    transport.close();
+}
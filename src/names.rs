@@ -0,0 +1,133 @@
+//! pub mod names: what a player may be called
+//! names are shown on the board and in the chat, so they are short and plain:
+//! letters, digits, spaces and `-_.'()`, no more than `MAX_NAME_LEN` characters
+//! the client checks a name when it is typed, and the server cleans up whatever comes,
+//! giving a suffix such as `Ann (2)` to a name already in the yard
+
+use serde::{ Deserialize, Serialize };
+
+use std::fmt;
+
+pub const MAX_NAME_LEN: usize = 20;
+/// allowed besides letters, digits and spaces
+pub const NAME_PUNCTUATION: &str = "-_.'()";
+
+/// reasons why a name is not allowed
+#[derive(Debug, PartialEq)]
+pub enum NameError {
+    Empty,
+    /// with the length
+    TooLong(usize),
+    /// with the first character not allowed
+    BadChar(char),
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NameError::Empty
+                => write!(f, "the name is empty"),
+            NameError::TooLong(len)
+                => write!(f, "the name has {} characters, at most {} are allowed", len, MAX_NAME_LEN),
+            NameError::BadChar(c)
+                => write!(f, "{:?} is not allowed, use letters, digits, spaces and {}", c, NAME_PUNCTUATION),
+        }
+    }
+}
+
+impl std::error::Error for NameError {}
+
+/// why the server gave a player another name than the one wanted
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RenameReason {
+    /// what is not allowed was dropped, or the name was cut short
+    Sanitized,
+    /// someone in the yard has it already, so it got a suffix
+    Taken,
+}
+
+fn allowed(c: char) -> bool {
+    c.is_alphanumeric() || c == ' ' || NAME_PUNCTUATION.contains(c)
+}
+
+/// check a name as typed, giving it back without the spaces around
+pub fn validate_name(name: &str) -> Result<String, NameError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(NameError::Empty);
+    }
+    if let Some(c) = name.chars().find(|c| !allowed(*c)) {
+        return Err(NameError::BadChar(c));
+    }
+    let len = name.chars().count();
+    if len > MAX_NAME_LEN {
+        return Err(NameError::TooLong(len));
+    }
+    Ok(name.to_string())
+}
+
+/// make the best of any name, dropping what is not allowed and cutting it short
+/// `None` if nothing is left
+pub fn sanitize_name(name: &str) -> Option<String> {
+    let kept: String = name.chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .filter(|c| allowed(*c))
+        .collect();
+    // spaces in a row are one
+    let words: Vec<&str> = kept.split(' ').filter(|w| !w.is_empty()).collect();
+    let name: String = words.join(" ").chars().take(MAX_NAME_LEN).collect();
+    let name = name.trim_end();
+    if name.is_empty() { None } else { Some(name.to_string()) }
+}
+
+/// the name, or with the first suffix free if someone in the yard has it already
+/// names are compared regardless of case
+pub fn unique_name<'a>(name: &str, taken: impl Iterator<Item = &'a str> + Clone) -> String {
+    let is_taken = |n: &str| taken.clone().any(|t| t.to_lowercase() == n.to_lowercase());
+    if !is_taken(name) {
+        return name.to_string();
+    }
+    let mut i = 2;
+    loop {
+        let suffix = format!(" ({})", i);
+        let base: String = name.chars().take(MAX_NAME_LEN - suffix.len()).collect();
+        let candidate = format!("{}{}", base.trim_end(), suffix);
+        if !is_taken(&candidate) {
+            return candidate;
+        }
+        i += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_name_keeps_plain_names() {
+        assert_eq!(validate_name("  Ann-Marie (2) "), Ok(String::from("Ann-Marie (2)")));
+        assert_eq!(validate_name("   "), Err(NameError::Empty));
+        assert_eq!(validate_name("Ann!"), Err(NameError::BadChar('!')));
+        assert_eq!(validate_name(&"a".repeat(MAX_NAME_LEN + 1)), Err(NameError::TooLong(MAX_NAME_LEN + 1)));
+        assert!(validate_name(&"é".repeat(MAX_NAME_LEN)).is_ok());
+    }
+
+    #[test]
+    fn sanitize_name_makes_the_best_of_it() {
+        assert_eq!(sanitize_name("\tAnn\n\x1b[31mred!  "), Some(String::from("Ann 31mred")));
+        assert_eq!(sanitize_name("Ann    Bob"), Some(String::from("Ann Bob")));
+        assert_eq!(sanitize_name("!!!"), None);
+        assert_eq!(sanitize_name(&"abc ".repeat(10)).unwrap().chars().count(), MAX_NAME_LEN - 1);
+    }
+
+    #[test]
+    fn unique_name_takes_the_first_suffix_free() {
+        let taken = ["Ann", "ann (2)", "Bob"];
+        assert_eq!(unique_name("Eve", taken.iter().copied()), "Eve");
+        assert_eq!(unique_name("ANN", taken.iter().copied()), "ANN (3)");
+        let long = "a".repeat(MAX_NAME_LEN);
+        let unique = unique_name(&long, [long.as_str()].into_iter());
+        assert_eq!(unique.chars().count(), MAX_NAME_LEN);
+        assert!(unique.ends_with(" (2)"));
+    }
+}
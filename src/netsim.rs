@@ -0,0 +1,328 @@
+//! pub mod netsim: bad network conditions on purpose, for testing on a single machine
+//! `SimClient` wraps the client's end of a connection, both ways:
+//!  - controls go as by TCP, late but never lost or out of order
+//!  - infos go as by UDP, late, lost, doubled or out of order
+//!
+//! conditions are given as a preset, options, or both, such as `wifi,loss=10`:
+//!  - `latency=<ms>` and `jitter=<ms>`: each way, a message is late by the latency give or take the jitter
+//!  - `loss=<%>`, `dup=<%>` and `reorder=<%>`: chances of an info being lost, doubled or overtaken
+//!  - `seed=<n>`: the same seed makes the same bad luck
+//!  - presets: `lan`, `wifi` and `mobile`
+
+use crate::server::{ YardCtrl, YardInfo };
+use crate::transport::{ ClientTransport, LinkStats, SeqTracker };
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use std::fmt;
+use std::io::{ self, Error, ErrorKind };
+use std::thread;
+use std::sync::{ Arc, Mutex };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::mpsc::{ self, Sender, Receiver, RecvTimeoutError };
+use std::time::{ Duration, Instant };
+
+/// an info overtaken is held back this much longer than the others
+pub const REORDER_DELAY: Duration = Duration::from_millis(50);
+
+/// how bad the network is
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct NetConditions {
+    /// one way
+    pub latency: Duration,
+    /// the latency varies by at most this, either way
+    pub jitter: Duration,
+    /// chances from 0 to 1
+    pub loss: f64,
+    pub duplicate: f64,
+    pub reorder: f64,
+    pub seed: Option<u64>,
+}
+
+/// reasons why conditions can't be understood
+#[derive(Debug)]
+pub enum NetSimError {
+    /// neither a preset nor an option
+    Unknown(String),
+    /// an option with a value out of range or not a number
+    BadValue(String),
+}
+
+impl fmt::Display for NetSimError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetSimError::Unknown(o)
+                => write!(f, "unknown network condition \"{}\", try lan, wifi, mobile, latency, jitter, loss, dup, reorder or seed", o),
+            NetSimError::BadValue(o)
+                => write!(f, "bad value in \"{}\", times are in ms and chances in % from 0 to 100", o),
+        }
+    }
+}
+
+impl std::error::Error for NetSimError {}
+
+impl NetConditions {
+    /// the network conditions known by name
+    pub fn preset(name: &str) -> Option<NetConditions> {
+        let (latency, jitter, loss) = match name {
+            "lan" => (1, 1, 0.0),
+            "wifi" => (40, 30, 0.02),
+            "mobile" => (120, 60, 0.05),
+            _ => return None,
+        };
+        Some(NetConditions {
+            latency: Duration::from_millis(latency),
+            jitter: Duration::from_millis(jitter),
+            loss,
+            reorder: loss / 2.0,
+            ..NetConditions::default()
+        })
+    }
+
+    /// understand conditions as in the module document
+    pub fn parse(spec: &str) -> Result<NetConditions, NetSimError> {
+        let mut c = NetConditions::default();
+        for option in spec.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+            let (key, value) = match option.split_once('=') {
+                Some(kv) => kv,
+                None => {
+                    c = NetConditions::preset(option).ok_or_else(|| NetSimError::Unknown(option.to_string()))?;
+                    continue;
+                },
+            };
+            let bad_value = || NetSimError::BadValue(option.to_string());
+            let number = value.trim().parse::<u64>().map_err(|_e| bad_value());
+            let chance = || match number {
+                Ok(n) if n <= 100 => Ok(n as f64 / 100.0),
+                _ => Err(bad_value()),
+            };
+            match key.trim() {
+                "latency" => { c.latency = Duration::from_millis(number?); },
+                "jitter" => { c.jitter = Duration::from_millis(number?); },
+                "loss" => { c.loss = chance()?; },
+                "dup" => { c.duplicate = chance()?; },
+                "reorder" => { c.reorder = chance()?; },
+                "seed" => { c.seed = Some(number?); },
+                _ => { return Err(NetSimError::Unknown(option.to_string())); },
+            }
+        }
+        Ok(c)
+    }
+}
+
+/// carries messages from one end to the other under the conditions, on its own thread
+/// `ordered`: as by TCP, nothing is lost, doubled or overtaken, it is only late
+fn delay_line<T: Clone + Send + 'static>(
+        conditions: NetConditions,
+        mut rng: StdRng,
+        ordered: bool,
+    ) -> (Sender<T>, Receiver<T>) {
+    let (in_tx, in_rx) = mpsc::channel::<T>();
+    let (out_tx, out_rx) = mpsc::channel();
+    thread::spawn(move || {
+        // on the way, with when they arrive
+        let mut flying: Vec<(Instant, T)> = Vec::new();
+        let mut last_due = Instant::now();
+        let mut open = true;
+        while open || !flying.is_empty() {
+            let now = Instant::now();
+            // deliver the ones arrived, the earliest first
+            while let Some(i) = (0..flying.len()).filter(|i| flying[*i].0 <= now).min_by_key(|i| flying[*i].0) {
+                if out_tx.send(flying.remove(i).1).is_err() {
+                    return;
+                }
+            }
+            let wait = flying.iter().map(|f| f.0.saturating_duration_since(now)).min()
+                .unwrap_or(Duration::from_millis(10));
+            if !open {
+                thread::sleep(wait);
+                continue;
+            }
+            let message = match in_rx.recv_timeout(wait) {
+                Ok(m) => m,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => { open = false; continue; },
+            };
+            let copies = if ordered {
+                1
+            } else if rng.gen_bool(conditions.loss) {
+                0
+            } else if rng.gen_bool(conditions.duplicate) {
+                2
+            } else {
+                1
+            };
+            for _copy in 0..copies {
+                let late = conditions.latency.as_secs_f64()
+                    + conditions.jitter.as_secs_f64() * rng.gen_range(-1.0..=1.0);
+                let mut due = Instant::now() + Duration::from_secs_f64(late.max(0.0));
+                if ordered {
+                    due = due.max(last_due);
+                    last_due = due;
+                } else if rng.gen_bool(conditions.reorder) {
+                    due += conditions.jitter + REORDER_DELAY;
+                }
+                flying.push((due, message.clone()));
+            }
+        }
+    });
+    (in_tx, out_rx)
+}
+
+/// the client's end of a connection, under bad conditions
+pub struct SimClient {
+    inner: Arc<dyn ClientTransport>,
+    ctrl_tx: Mutex<Sender<YardCtrl>>,
+    /// infos numbered again after the inner end, to count what the simulation loses
+    info_rx: Mutex<Receiver<(u64, YardInfo)>>,
+    tracker: Mutex<SeqTracker>,
+    closed: Arc<AtomicBool>,
+}
+
+impl SimClient {
+    /// put the connection `inner` under the conditions
+    pub fn wrap(inner: Arc<dyn ClientTransport>, conditions: NetConditions) -> SimClient {
+        let mut rng = match conditions.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let (ctrl_tx, ctrl_rx) = delay_line::<YardCtrl>(conditions, StdRng::seed_from_u64(rng.gen()), true);
+        let (info_tx, info_rx) = delay_line(conditions, rng, false);
+        let closed = Arc::new(AtomicBool::new(false));
+        // controls arrived go on
+        let ctrl_inner = Arc::clone(&inner);
+        thread::spawn(move || {
+            while let Ok(ctrl) = ctrl_rx.recv() {
+                if ctrl_inner.send_ctrl(ctrl).is_err() {
+                    break;
+                }
+            }
+        });
+        // infos are taken from the inner end as soon as they come
+        let info_inner = Arc::clone(&inner);
+        let info_closed = Arc::clone(&closed);
+        thread::spawn(move || {
+            let mut seq = 0;
+            while !info_closed.load(Ordering::Relaxed) {
+                match info_inner.recv_info(Duration::from_millis(10)) {
+                    Ok(Some(info)) => {
+                        if info_tx.send((seq, info)).is_err() {
+                            break;
+                        }
+                        seq += 1;
+                    },
+                    Ok(None) => {},
+                    Err(_e) => break, // the line is closed, and so is the wrapper
+                }
+            }
+        });
+        SimClient {
+            inner,
+            ctrl_tx: Mutex::new(ctrl_tx),
+            info_rx: Mutex::new(info_rx),
+            tracker: Mutex::new(SeqTracker::default()),
+            closed,
+        }
+    }
+}
+
+impl ClientTransport for SimClient {
+    fn send_ctrl(&self, ctrl: YardCtrl) -> io::Result<()> {
+        self.ctrl_tx.lock().unwrap().send(ctrl)
+            .map_err(|_e| Error::new(ErrorKind::NotConnected, "the other end is gone"))
+    }
+
+    fn recv_info(&self, timeout: Duration) -> io::Result<Option<YardInfo>> {
+        match self.info_rx.lock().unwrap().recv_timeout(timeout) {
+            Ok((seq, info)) => {
+                self.tracker.lock().unwrap().count(seq);
+                Ok(Some(info))
+            },
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::new(ErrorKind::NotConnected, "the other end is gone")),
+        }
+    }
+
+    /// what is lost on the real link and in the simulation
+    fn link_stats(&self) -> LinkStats {
+        let (real, simulated) = (self.inner.link_stats(), self.tracker.lock().unwrap().stats);
+        LinkStats {
+            received: simulated.received,
+            dropped: real.dropped + simulated.dropped,
+            out_of_order: real.out_of_order + simulated.out_of_order,
+            duplicated: real.duplicated + simulated.duplicated,
+        }
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.inner.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// what comes out of a line that is given the numbers 0 to `count`, then closed
+    fn carried(conditions: NetConditions, ordered: bool, count: u32) -> Vec<u32> {
+        let (tx, rx) = delay_line(conditions, StdRng::seed_from_u64(7), ordered);
+        for n in 0..count {
+            tx.send(n).unwrap();
+        }
+        drop(tx);
+        rx.iter().collect()
+    }
+
+    fn bad(spec: &str) -> NetConditions {
+        NetConditions::parse(spec).unwrap()
+    }
+
+    #[test]
+    fn parse_preset_then_options() {
+        let c = NetConditions::parse("wifi, loss=10,seed=7").unwrap();
+        assert_eq!(c.latency, Duration::from_millis(40));
+        assert_eq!(c.jitter, Duration::from_millis(30));
+        assert_eq!(c.loss, 0.1);
+        assert_eq!(c.seed, Some(7));
+        assert_eq!(NetConditions::parse("").unwrap(), NetConditions::default());
+    }
+
+    #[test]
+    fn parse_rejects_what_is_not_understood() {
+        assert!(matches!(NetConditions::parse("satellite"), Err(NetSimError::Unknown(_))));
+        assert!(matches!(NetConditions::parse("speed=3"), Err(NetSimError::Unknown(_))));
+        assert!(matches!(NetConditions::parse("loss=101"), Err(NetSimError::BadValue(_))));
+        assert!(matches!(NetConditions::parse("latency=-5"), Err(NetSimError::BadValue(_))));
+        assert!(matches!(NetConditions::parse("jitter=fast"), Err(NetSimError::BadValue(_))));
+    }
+
+    #[test]
+    fn lost_ones_never_come() {
+        assert!(carried(bad("loss=100"), false, 20).is_empty());
+    }
+
+    #[test]
+    fn doubled_ones_come_twice() {
+        let mut came = carried(bad("dup=100,jitter=5"), false, 20);
+        came.sort_unstable();
+        let twice: Vec<u32> = (0..20).flat_map(|n| [n, n]).collect();
+        assert_eq!(came, twice);
+    }
+
+    #[test]
+    fn overtaken_ones_come_late() {
+        let came = carried(bad("latency=5,jitter=5,reorder=50"), false, 50);
+        assert!(came.windows(2).any(|w| w[0] > w[1]));
+        let mut sorted = came.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..50).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn ordered_line_keeps_the_order() {
+        let conditions = bad("latency=5,jitter=5,loss=100,dup=100,reorder=100");
+        assert_eq!(carried(conditions, true, 50), (0..50).collect::<Vec<u32>>());
+    }
+}
@@ -0,0 +1,216 @@
+//! pub mod predict: showing the own snake's turns before the server takes them
+//! over a slow link, a turn is seen only after the server has ticked and the frame has come back,
+//! so the client draws the turn on the last frame right away, and gives up the guess
+//! once the server has taken the turn, or when it seems the turn never arrived
+
+use crate::render::{ TUIBlock, YardBuf, EMPTY, HEAD_L, HEAD_R, HEAD_U, HEAD_D };
+use crate::yard::{ Direction, YardState, MAX_PENDING_TURNS, SPEED_UNIT };
+
+use crossterm::style::Color;
+
+use std::collections::VecDeque;
+use std::time::{ Duration, Instant };
+
+/// a turn not taken by the server within this long is thought to be lost
+pub const PREDICTION_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// guesses where the own snake goes, reconciled with every state from the server
+pub struct Predictor {
+    /// the field id of the own snake, while it is alive
+    me: Option<u8>,
+    /// the last frame and state from the server
+    buf: Option<YardBuf>,
+    state: Option<YardState>,
+    /// turns sent but not seen taken yet, with when they were sent and the server tick then
+    turns: VecDeque<(Direction, Instant, u64)>,
+}
+
+impl Predictor {
+    /// `me`: the field id, if the snake is already spawned
+    pub fn new(me: Option<u8>) -> Predictor {
+        Predictor { me, buf: None, state: None, turns: VecDeque::new() }
+    }
+
+    /// the own snake is spawned, or failed with `None`
+    pub fn set_me(&mut self, me: Option<u8>) {
+        self.me = me;
+        self.turns.clear();
+    }
+
+    /// whether some turn is shown before the server took it
+    pub fn pending(&self) -> bool {
+        !self.turns.is_empty()
+    }
+
+    /// a turn is sent to the server, returns whether the server is expected to take it
+    /// the rules are the same as `YardSim::control_snake`
+    pub fn turn(&mut self, d: Direction) -> bool {
+        let (current, tick) = match (self.me, &self.state) {
+            (Some(me), Some(state)) => match state.snake(me) {
+                Some(s) => (s.direction, state.tick),
+                None => return false,
+            },
+            _ => return false,
+        };
+        let last = self.turns.back().map(|t| t.0).unwrap_or(current);
+        if d == last || d == last.opposite() || self.turns.len() >= MAX_PENDING_TURNS {
+            return false;
+        }
+        self.turns.push_back((d, Instant::now(), tick));
+        true
+    }
+
+    /// a frame from the server
+    pub fn frame(&mut self, buf: YardBuf) {
+        self.buf = Some(buf);
+    }
+
+    /// a state from the server, the turns it has taken are no longer guessed
+    pub fn reconcile(&mut self, state: YardState) {
+        let now = Instant::now();
+        self.turns.retain(|t| now.duration_since(t.1) < PREDICTION_TIMEOUT);
+        match self.me.and_then(|me| state.snake(me)) {
+            Some(s) => {
+                // the snake takes a queued turn at most each block it moves, and only after it was sent,
+                // so the guesses matched are the first ones it may have taken since the last state,
+                // more than one if states were lost on the way, as a later turn taken means the earlier ones too
+                let ticks = match &self.state {
+                    Some(last) => state.tick.saturating_sub(last.tick),
+                    None => u64::MAX,
+                };
+                let blocks = ticks.saturating_mul(s.speed.div_ceil(SPEED_UNIT) as u64);
+                let taken = self.turns.iter()
+                    .take(usize::try_from(blocks).unwrap_or(usize::MAX))
+                    .take_while(|t| t.2 < state.tick)
+                    .position(|t| t.0 == s.direction);
+                if let Some(i) = taken {
+                    self.turns.drain(..=i);
+                }
+            },
+            None => { self.turns.clear(); },
+        }
+        self.state = Some(state);
+    }
+
+    /// the last frame, with the own snake moved a block by the first turn not taken yet
+    pub fn view(&self) -> Option<YardBuf> {
+        let mut buf = self.buf.clone()?;
+        self.predict(&mut buf);
+        Some(buf)
+    }
+
+    /// move the own snake on the frame, `None` if there is nothing to guess
+    fn predict(&self, buf: &mut YardBuf) -> Option<()> {
+        let (d, _at, _tick) = self.turns.front()?;
+        let state = self.state.as_ref()?;
+        let s = state.snake(self.me?)?;
+        if s.stalled || buf.len() != state.height || buf[0].len() != state.width {
+            return None;
+        }
+        let head = s.body[0];
+        let next = state.next_pos(head, *d)?;
+        let bg = buf[head.0][head.1].bg;
+        // the head leaves a body behind, and the tail retracts unless a bean is eaten
+        buf[head.0][head.1] = TUIBlock { fg: Color::White, bg, content: EMPTY.to_string() };
+        if !state.beans.contains(&next) {
+            let tail = s.body[s.body.len() - 1];
+            buf[tail.0][tail.1] = TUIBlock { fg: Color::White, bg: Color::White, content: EMPTY.to_string() };
+        }
+        buf[next.0][next.1] = TUIBlock {
+            fg: Color::White,
+            bg,
+            content: match d {
+                Direction::L => HEAD_L.to_string(),
+                Direction::R => HEAD_R.to_string(),
+                Direction::U => HEAD_U.to_string(),
+                Direction::D => HEAD_D.to_string(),
+            },
+        };
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yard::{ GameConfig, YardSim };
+
+    /// a state with one snake, and a turn across its way
+    fn start() -> (Predictor, YardState, Direction) {
+        let mut y = YardSim::with_seed(&GameConfig::default(), 1);
+        let me = y.init_snake();
+        let mut state = y.snapshot();
+        state.tick = 5;
+        state.snakes[0].stalled = false;
+        let across = match state.snakes[0].direction {
+            Direction::L | Direction::R => Direction::U,
+            Direction::U | Direction::D => Direction::L,
+        };
+        let mut p = Predictor::new(me);
+        p.reconcile(state.clone());
+        (p, state, across)
+    }
+
+    fn later(state: &YardState, ticks: u64, d: Direction) -> YardState {
+        let mut state = state.clone();
+        state.tick += ticks;
+        state.snakes[0].direction = d;
+        state
+    }
+
+    #[test]
+    fn turn_follows_the_rules() {
+        let (mut p, state, across) = start();
+        let current = state.snakes[0].direction;
+        assert!(!p.turn(current));
+        assert!(!p.turn(current.opposite()));
+        assert!(p.turn(across));
+        assert!(!p.turn(across));
+        assert!(p.pending());
+    }
+
+    #[test]
+    fn guess_is_given_up_once_taken() {
+        let (mut p, state, across) = start();
+        p.turn(across);
+        p.reconcile(later(&state, 1, state.snakes[0].direction));
+        assert!(p.pending());
+        p.reconcile(later(&state, 2, across));
+        assert!(!p.pending());
+    }
+
+    #[test]
+    fn later_turn_is_not_matched_too_soon() {
+        let (mut p, state, across) = start();
+        let current = state.snakes[0].direction;
+        p.turn(across);
+        p.turn(current);
+        // the snake took the first turn, it can't have taken the second one in the same block
+        p.reconcile(later(&state, 1, across));
+        assert!(p.pending());
+        p.reconcile(later(&state, 2, current));
+        assert!(!p.pending());
+    }
+
+    #[test]
+    fn lost_states_match_several_turns() {
+        let (mut p, state, across) = start();
+        let current = state.snakes[0].direction;
+        p.turn(across);
+        p.turn(current);
+        p.reconcile(later(&state, 2, current));
+        assert!(!p.pending());
+    }
+
+    #[test]
+    fn view_moves_the_head() {
+        let (mut p, state, across) = start();
+        let blank = TUIBlock { fg: Color::White, bg: Color::White, content: EMPTY.to_string() };
+        p.frame(vec![vec![blank; state.width]; state.height]);
+        assert!(p.turn(across));
+        let head = state.snakes[0].body[0];
+        let next = state.next_pos(head, across).unwrap();
+        let view = p.view().unwrap();
+        assert_ne!(view[next.0][next.1].content, EMPTY);
+    }
+}
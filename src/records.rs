@@ -0,0 +1,228 @@
+//! pub mod records: the host's leaderboard and match history, kept between games
+//! the records are a JSON file `socket_snake/records.json` in the XDG data directory,
+//! `$XDG_DATA_HOME` or else `~/.local/share`, written by the server whenever a game ends
+//! a game is a life in the endless game, or a round in a match; bots are not recorded
+
+use crate::server::BoardType;
+use crate::settings;
+
+use crossterm::style::Color;
+use serde::{ Deserialize, Serialize };
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+pub const RECORDS_FILE: &str = "records.json";
+/// the history keeps this many games, the latest last
+pub const MAX_HISTORY: usize = 100;
+/// the leaderboard sent after a game shows this many players
+pub const LEADERBOARD_LEN: usize = 5;
+
+/// reasons why the records can't be loaded or saved
+#[derive(Debug)]
+pub enum RecordsError {
+    /// neither `$XDG_DATA_HOME` nor `$HOME` is set
+    NoDataDir,
+    Io(std::io::Error),
+    BadFile(serde_json::Error),
+}
+
+impl fmt::Display for RecordsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordsError::NoDataDir
+                => write!(f, "there is no data directory, set $XDG_DATA_HOME or $HOME"),
+            RecordsError::Io(e)
+                => write!(f, "can't read or write the records: {}", e),
+            RecordsError::BadFile(e)
+                => write!(f, "the records file is not understood: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RecordsError {}
+
+/// how a player did in one game
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameStats {
+    /// the highest score reached, before the snake died
+    pub score: usize,
+    pub kills: usize,
+    /// the longest the snake has been, in blocks
+    pub length: usize,
+}
+
+impl GameStats {
+    /// the snake is this long with this score now
+    pub fn update(&mut self, score: usize, length: usize) {
+        self.score = self.score.max(score);
+        self.length = self.length.max(length);
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} points, {} kills, {} blocks long", self.score, self.kills, self.length)
+    }
+}
+
+/// how a player did in all the games on this host
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    pub name: String,
+    pub high_score: usize,
+    /// in all the games
+    pub kills: usize,
+    pub games: usize,
+    pub longest: usize,
+}
+
+/// a game that was played, with how everyone did
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatchRecord {
+    /// seconds since the unix epoch
+    pub ended: u64,
+    /// the match mode, as `MatchMode` is written
+    pub mode: String,
+    pub results: Vec<(String, GameStats)>,
+}
+
+/// the leaderboard and the history of a host
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Records {
+    pub players: Vec<PlayerRecord>,
+    pub history: VecDeque<MatchRecord>,
+}
+
+/// `records.json` in the XDG data directory
+pub fn records_path() -> Option<PathBuf> {
+    Some(settings::xdg_dir("XDG_DATA_HOME", ".local/share")?.join(RECORDS_FILE))
+}
+
+impl Records {
+    /// the records in the file, none if there is no file yet
+    pub fn load_from(path: &Path) -> Result<Records, RecordsError> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(RecordsError::BadFile),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Records::default()),
+            Err(e) => Err(RecordsError::Io(e)),
+        }
+    }
+
+    /// the records of this computer, see `records_path`
+    pub fn load() -> Result<Records, RecordsError> {
+        Records::load_from(&records_path().ok_or(RecordsError::NoDataDir)?)
+    }
+
+    /// write the records, making the directory if needed
+    pub fn save_to(&self, path: &Path) -> Result<(), RecordsError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(RecordsError::Io)?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(RecordsError::BadFile)?;
+        fs::write(path, text).map_err(RecordsError::Io)
+    }
+
+    /// the record of a player, names are compared regardless of case as they are unique so
+    pub fn player(&self, name: &str) -> Option<&PlayerRecord> {
+        self.players.iter().find(|p| p.name.to_lowercase() == name.to_lowercase())
+    }
+
+    /// a game ended, count it for everyone in it
+    pub fn record(&mut self, mode: &str, results: Vec<(String, GameStats)>) {
+        for (name, stats) in &results {
+            let i = match self.players.iter().position(|p| p.name.to_lowercase() == name.to_lowercase()) {
+                Some(i) => i,
+                None => {
+                    self.players.push(PlayerRecord { name: name.clone(), ..PlayerRecord::default() });
+                    self.players.len() - 1
+                },
+            };
+            let p = &mut self.players[i];
+            p.high_score = p.high_score.max(stats.score);
+            p.kills += stats.kills;
+            p.games += 1;
+            p.longest = p.longest.max(stats.length);
+        }
+        self.history.push_back(MatchRecord {
+            ended: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            mode: mode.to_string(),
+            results,
+        });
+        while self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+    }
+
+    /// the players by high score, the best first
+    pub fn ranking(&self) -> Vec<&PlayerRecord> {
+        let mut ranking: Vec<&PlayerRecord> = self.players.iter().collect();
+        ranking.sort_by_key(|p| (std::cmp::Reverse(p.high_score), std::cmp::Reverse(p.kills)));
+        ranking
+    }
+
+    /// the best `n` players as a board, with their high score, kills, games and longest snake
+    pub fn leaderboard(&self, n: usize) -> BoardType {
+        let mut board = vec![(Color::Black, String::from("Leaderboard\n"))];
+        for (place, p) in self.ranking().iter().take(n).enumerate() {
+            board.push((
+                if place == 0 { Color::DarkYellow } else { Color::DarkGrey },
+                format!(
+                    "{}. {}: {} points, {} kills in {} games, {} blocks long\n",
+                    place + 1, p.name, p.high_score, p.kills, p.games, p.longest,
+                ),
+            ));
+        }
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(score: usize, kills: usize, length: usize) -> GameStats {
+        GameStats { score, kills, length }
+    }
+
+    #[test]
+    fn record_adds_up_by_player() {
+        let mut records = Records::default();
+        records.record("Endless", vec![(String::from("Ann"), stats(5, 1, 8)), (String::from("Bob"), stats(9, 0, 12))]);
+        records.record("Endless", vec![(String::from("ann"), stats(3, 2, 10))]);
+        let ann = records.player("ANN").unwrap();
+        assert_eq!((ann.high_score, ann.kills, ann.games, ann.longest), (5, 3, 2, 10));
+        let ranking: Vec<&str> = records.ranking().iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(ranking, vec!["Bob", "Ann"]);
+        assert_eq!(records.history.len(), 2);
+        assert_eq!(records.leaderboard(1).len(), 2);
+    }
+
+    #[test]
+    fn history_keeps_the_latest() {
+        let mut records = Records::default();
+        for score in 0..MAX_HISTORY + 3 {
+            records.record("Endless", vec![(String::from("Ann"), stats(score, 0, 1))]);
+        }
+        assert_eq!(records.history.len(), MAX_HISTORY);
+        assert_eq!(records.history.front().unwrap().results[0].1.score, 3);
+    }
+
+    #[test]
+    fn records_are_saved_and_loaded() {
+        let dir = std::env::temp_dir().join(format!("socket_snake_records_{}", std::process::id()));
+        let path = dir.join("records").join(RECORDS_FILE);
+        assert!(Records::load_from(&path).unwrap().players.is_empty());
+        let mut records = Records::default();
+        records.record("Rounds(3)", vec![(String::from("Ann"), stats(5, 1, 8))]);
+        records.save_to(&path).unwrap();
+        let loaded = Records::load_from(&path).unwrap();
+        assert_eq!(loaded.players, records.players);
+        assert_eq!(loaded.history, records.history);
+        fs::write(&path, "[").unwrap();
+        assert!(matches!(Records::load_from(&path), Err(RecordsError::BadFile(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,15 +2,18 @@
 
 use crate::server::BoardType;
 
-pub use std::io::{stdout};
+pub use std::io::{stdout, Write};
 pub use crossterm::{
     ExecutableCommand, QueueableCommand, Result,
-    terminal::{Clear, ClearType},
+    terminal::{self, Clear, ClearType},
     cursor,
     style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
 };
 use serde::{Deserialize, Serialize};
 
+use std::fs::File;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
 /// two character-wide basic TUI blocks, which may appear in the game
 pub const HEAD_L: &str  = ": ";
 pub const HEAD_R: &str  = " :";
@@ -18,7 +21,21 @@ pub const HEAD_U: &str  = "''";
 pub const HEAD_D: &str  = "..";
 pub const BEAN: &str    = "()";
 pub const FENCE: &str   = "[]";
+pub const FENCE_DASHED: &str = "- ";
+pub const ITEM_SPEED: &str      = ">>";
+pub const ITEM_GHOST: &str      = "~~";
+pub const ITEM_SHRINK: &str     = "><";
+pub const ITEM_MULTIPLIER: &str = "x2";
+pub const ITEM_SHIELD: &str     = "<>";
+/// shown on the board for a boosting snake
+pub const BOOST_TAG: &str       = "=>";
 pub const EMPTY: &str   = "  ";
+/// bodies are colored empty blocks, the text frames need something to see
+pub const BODY_TEXT: &str = "oo";
+/// the chat panel is on the right of the board, from this column
+pub const CHAT_COLUMN: u16 = 36;
+/// and each line of it is cut to this many characters
+pub const CHAT_WIDTH: usize = 60;
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TUIBlock {
     pub fg: Color,
@@ -28,11 +45,17 @@ pub struct TUIBlock {
 
 // print a block after the curser
 pub fn put_tui_block(block: &TUIBlock) -> Result<()> {
-    stdout()
-        .execute(SetForegroundColor(block.fg))?
-        .execute(SetBackgroundColor(block.bg))?
-        .execute(Print(&block.content))?
-        .execute(ResetColor)?;
+    queue_tui_block(&mut stdout(), block)?;
+    stdout().flush()?;
+    Ok(())
+}
+
+/// queue a block into any writer, the caller decides when to flush
+pub fn queue_tui_block<W: Write>(w: &mut W, block: &TUIBlock) -> Result<()> {
+    w.queue(SetForegroundColor(block.fg))?
+        .queue(SetBackgroundColor(block.bg))?
+        .queue(Print(&block.content))?
+        .queue(ResetColor)?;
     Ok(())
 }
 
@@ -46,6 +69,51 @@ pub fn clear_screen_and_go_back() -> Result<()> {
 /// the buffer that is sended to the clients
 pub type YardBuf = Vec<Vec<TUIBlock>>;
 
+/// how the terminal paints the colors the server sends, chosen by each player
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    /// the colors as they are, on a white field
+    #[default]
+    Classic,
+    /// a black field, for dark terminals
+    Dark,
+    /// bright snakes on the white field, easier to tell apart
+    HighContrast,
+}
+
+pub const THEMES: [Theme; 3] = [Theme::Classic, Theme::Dark, Theme::HighContrast];
+
+impl Theme {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Classic => "Classic",
+            Theme::Dark => "Dark",
+            Theme::HighContrast => "High contrast",
+        }
+    }
+
+    /// the color a background is painted with
+    pub fn paint(&self, color: Color) -> Color {
+        match (self, color) {
+            (Theme::Dark, Color::White) => Color::Black,
+            (Theme::Dark, Color::Grey) => Color::DarkGrey,
+            (Theme::Dark, Color::DarkGrey) => Color::Grey,
+            (Theme::HighContrast, Color::DarkGrey) => Color::Black,
+            (Theme::HighContrast, Color::DarkRed) => Color::Red,
+            (Theme::HighContrast, Color::DarkBlue) => Color::Blue,
+            (Theme::HighContrast, Color::DarkMagenta) => Color::Magenta,
+            (Theme::HighContrast, Color::DarkCyan) => Color::Cyan,
+            (Theme::HighContrast, Color::DarkGreen) => Color::Green,
+            _ => color,
+        }
+    }
+
+    /// the block with its background painted
+    pub fn paint_block(&self, block: &TUIBlock) -> TUIBlock {
+        TUIBlock { bg: self.paint(block.bg), ..block.clone() }
+    }
+}
+
 pub fn width(b: &YardBuf) -> usize {
     b[0].len()
 }
@@ -54,38 +122,165 @@ pub fn height(b: &YardBuf) -> usize {
     b.len()
 }
 
+/// captures the terminal output with timestamps, and dumps it as an asciicast v2 file
+/// which can be replayed by `asciinema play` without the game installed
+pub struct AsciicastRecorder {
+    start: Instant,
+    timestamp: u64,
+    width: u16,
+    height: u16,
+    events: Vec<(f64, String)>,
+}
+
+impl AsciicastRecorder {
+    pub fn new() -> AsciicastRecorder {
+        let (width, height) = terminal::size().unwrap_or((80, 24));
+        AsciicastRecorder {
+            start: Instant::now(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs()).unwrap_or(0),
+            width, height,
+            events: Vec::new(),
+        }
+    }
+
+    /// record the bytes emitted to the terminal at this moment
+    pub fn record(&mut self, output: &[u8]) {
+        // the terminal translates "\n" to "\r\n" for us, but the player won't
+        let data = String::from_utf8_lossy(output).replace('\n', "\r\n");
+        self.events.push((self.start.elapsed().as_secs_f64(), data));
+    }
+
+    /// header line followed by one `[time, "o", data]` line per event
+    pub fn write_to<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let header = serde_json::json!({
+            "version": 2,
+            "width": self.width,
+            "height": self.height,
+            "timestamp": self.timestamp,
+            "title": "Socket Snake",
+        });
+        writeln!(w, "{}", header)?;
+        for (time, data) in &self.events {
+            writeln!(w, "{}", serde_json::to_string(&(time, "o", data))?)?;
+        }
+        w.flush()
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        self.write_to(&mut File::create(path)?)
+    }
+}
+
+impl Default for AsciicastRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// what the client shows the game on
+pub trait Frontend: Send {
+    /// draw the yard, only what changed if possible
+    fn draw_yard(&mut self, buf: YardBuf) -> Result<()>;
+    /// draw the board under the yard, followed by the footer
+    fn draw_board(&mut self, board: &BoardType) -> Result<()>;
+    /// show a message under the yard
+    fn show_info(&mut self, info: &str) -> Result<()>;
+    /// the round is over, show the scoreboard
+    fn round_over(&mut self, board: &BoardType) -> Result<()>;
+    /// the game is over for the player, with the reason
+    fn game_over(&mut self, info: &str) -> Result<()>;
+    /// draw passable borders as dashed fences
+    fn set_wrap_around(&mut self, wrap_around: bool) -> Result<()>;
+    /// keep a note under the board, empty for none
+    fn set_footer(&mut self, footer: &str);
+    /// keep a line of diagnostics under the footer, empty to hide it
+    fn set_hud(&mut self, hud: &str);
+    /// show the chat panel beside the board right away, and keep it there, empty to hide it
+    fn set_chat(&mut self, chat: &BoardType) -> Result<()>;
+    /// dump what was shown if it was recorded, nothing happens otherwise
+    fn save_recording(&self, _path: &str) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 pub struct TUIHelper {
     pub is_init: bool,
     pub buf: YardBuf,
+    pub recorder: Option<AsciicastRecorder>,
+    /// passable borders are drawn as dashed fences
+    pub wrap_around: bool,
+    /// a note kept under the board
+    pub footer: String,
+    /// diagnostics kept under the footer
+    pub hud: String,
+    /// the chat panel kept beside the board
+    pub chat: BoardType,
+    pub theme: Theme,
 }
 
 impl TUIHelper {
     pub fn new() -> TUIHelper {
-        return TUIHelper { is_init: false, buf: YardBuf::new() };
+        TUIHelper {
+            is_init: false,
+            buf: YardBuf::new(),
+            recorder: None,
+            wrap_around: false,
+            footer: String::new(),
+            hud: String::new(),
+            chat: BoardType::new(),
+            theme: Theme::Classic,
+        }
+    }
+
+    /// a helper painting with the theme
+    pub fn with_theme(self, theme: Theme) -> TUIHelper {
+        TUIHelper { theme, ..self }
+    }
+
+    /// a helper that also records everything it prints
+    pub fn with_recorder() -> TUIHelper {
+        TUIHelper { recorder: Some(AsciicastRecorder::new()), ..TUIHelper::new() }
+    }
+
+    /// write a whole frame to the terminal at once, and record it if asked to
+    fn emit(&mut self, frame: &[u8]) -> Result<()> {
+        let mut out = stdout();
+        out.write_all(frame)?;
+        out.flush()?;
+        if let Some(r) = &mut self.recorder {
+            r.record(frame);
+        }
+        Ok(())
     }
 
-    pub fn print_yard(&self) -> Result<()> {
-        stdout()
-            .execute(Clear(ClearType::All))?
-            .execute(cursor::MoveTo(0, 0))?;
-        let fence_block = TUIBlock { fg: Color::Black, bg: Color::Grey, content: FENCE.to_string() };
+    pub fn print_yard(&mut self) -> Result<()> {
+        let mut frame = Vec::<u8>::new();
+        frame
+            .queue(Clear(ClearType::All))?
+            .queue(cursor::MoveTo(0, 0))?;
+        let fence_block = self.theme.paint_block(&TUIBlock {
+            fg: Color::Black,
+            bg: Color::Grey,
+            content: if self.wrap_around { FENCE_DASHED } else { FENCE }.to_string(),
+        });
         for _i in 0..(width(&self.buf) + 2) {
-            put_tui_block(&fence_block)?;
+            queue_tui_block(&mut frame, &fence_block)?;
         }
-        stdout().queue(Print("\n"))?;
+        frame.queue(Print("\n"))?;
         for r in &self.buf {
-            put_tui_block(&fence_block)?;
+            queue_tui_block(&mut frame, &fence_block)?;
             for c in r {
-                put_tui_block(c)?;
+                queue_tui_block(&mut frame, &self.theme.paint_block(c))?;
             }
-            put_tui_block(&fence_block)?;
-            stdout().queue(Print("\n"))?;
+            queue_tui_block(&mut frame, &fence_block)?;
+            frame.queue(Print("\n"))?;
         }
         for _i in 0..(width(&self.buf) + 2) {
-            put_tui_block(&fence_block)?;
+            queue_tui_block(&mut frame, &fence_block)?;
         }
-        stdout().queue(Print("\n"))?;
-        Ok(())
+        frame.queue(Print("\n"))?;
+        self.emit(&frame)
     }
 
     pub fn refresh_yard(&mut self, nbuf: YardBuf) -> Result<()> {
@@ -94,49 +289,368 @@ impl TUIHelper {
             self.is_init = true;
             return self.print_yard();
         }
-        stdout().execute(cursor::Hide).unwrap();
-        for r in 0..height(&self.buf) {
-            for c in 0..width(&self.buf) {
-                if self.buf[r][c] != nbuf[r][c] {
-                    stdout()
-                        .execute(cursor::MoveTo(
+        let mut frame = Vec::<u8>::new();
+        frame.queue(cursor::Hide)?;
+        for (r, (old_row, new_row)) in self.buf.iter().zip(&nbuf).enumerate() {
+            for (c, (old, new)) in old_row.iter().zip(new_row).enumerate() {
+                if old != new {
+                    frame
+                        .queue(cursor::MoveTo(
                             (c * 2 + 2).try_into().unwrap(),
                             (r + 1).try_into().unwrap(),
                         ))?;
-                    put_tui_block(&nbuf[r][c])?;
+                    queue_tui_block(&mut frame, &self.theme.paint_block(new))?;
                 }
             }
         }
         self.buf = nbuf;
-        Ok(())
+        self.emit(&frame)
     }
 
     pub fn print_info(&mut self, info: &str) -> Result<()> {
-        stdout()
-            .execute(cursor::MoveTo(
+        let mut frame = Vec::<u8>::new();
+        frame
+            .queue(cursor::MoveTo(
                 0,
                 (height(&self.buf) + 2).try_into().unwrap(),
             ))?
-            .execute(Clear(ClearType::FromCursorDown))?
-            .execute(Print(info))?;
+            .queue(Clear(ClearType::FromCursorDown))?
+            .queue(Print(info))?;
+        self.queue_chat(&mut frame, 0)?;
+        self.emit(&frame)
+    }
+
+    /// the chat panel on the right of what is under the yard, the cursor is left where it was
+    /// `rows`: at least this many rows of the panel are cleared, for a longer panel drawn before
+    fn queue_chat(&self, frame: &mut Vec<u8>, rows: usize) -> Result<()> {
+        let title = (Color::Black, String::from("Chat"));
+        let lines: Vec<&(Color, String)> = if self.chat.is_empty() {
+            Vec::new()
+        } else {
+            std::iter::once(&title).chain(&self.chat).collect()
+        };
+        let top = height(&self.buf) + 2;
+        frame.queue(cursor::SavePosition)?;
+        for i in 0..rows.max(lines.len()) {
+            frame
+                .queue(cursor::MoveTo(CHAT_COLUMN, (top + i).try_into().unwrap()))?
+                .queue(Clear(ClearType::UntilNewLine))?;
+            if let Some(line) = lines.get(i) {
+                let text: String = line.1.trim_end().chars().take(CHAT_WIDTH).collect();
+                frame
+                    .queue(SetForegroundColor(Color::White))?
+                    .queue(SetBackgroundColor(self.theme.paint(line.0)))?
+                    .queue(Print(text))?
+                    .queue(ResetColor)?;
+            }
+        }
+        frame.queue(cursor::RestorePosition)?;
         Ok(())
     }
 
+    /// redraw the chat panel alone, see `queue_chat` for `rows`
+    pub fn print_chat(&mut self, rows: usize) -> Result<()> {
+        let mut frame = Vec::<u8>::new();
+        self.queue_chat(&mut frame, rows)?;
+        self.emit(&frame)
+    }
+
     pub fn print_board(&mut self, board: &BoardType) -> Result<()> {
-        stdout()
-            .execute(cursor::MoveTo(
+        let mut frame = Vec::<u8>::new();
+        frame
+            .queue(cursor::MoveTo(
                 0,
                 (height(&self.buf) + 2).try_into().unwrap(),
             ))?
-            .execute(Clear(ClearType::FromCursorDown))?
-            .execute(Print("Board\n"))?;
+            .queue(Clear(ClearType::FromCursorDown))?
+            .queue(Print("Board\n"))?;
+        for line in board {
+            frame
+                .queue(SetForegroundColor(Color::White))?
+                .queue(SetBackgroundColor(self.theme.paint(line.0)))?
+                .queue(Print(&line.1))?
+                .queue(ResetColor)?;
+        }
+        frame.queue(Print(&self.footer))?;
+        if !self.hud.is_empty() {
+            frame.queue(Print("\n"))?.queue(Print(&self.hud))?;
+        }
+        self.queue_chat(&mut frame, 0)?;
+        self.emit(&frame)
+    }
+
+    pub fn print_round_over(&mut self, board: &BoardType) -> Result<()> {
+        let mut frame = Vec::<u8>::new();
+        frame
+            .queue(cursor::MoveTo(
+                0,
+                (height(&self.buf) + 2).try_into().unwrap(),
+            ))?
+            .queue(Clear(ClearType::FromCursorDown))?
+            .queue(Print("Round over! Next round is coming soon\n"))?;
+        for line in board {
+            frame
+                .queue(SetForegroundColor(Color::White))?
+                .queue(SetBackgroundColor(self.theme.paint(line.0)))?
+                .queue(Print(&line.1))?
+                .queue(ResetColor)?;
+        }
+        self.queue_chat(&mut frame, 0)?;
+        self.emit(&frame)
+    }
+
+    /// switch the fence style, redraw if changed
+    pub fn set_wrap_around(&mut self, wrap_around: bool) -> Result<()> {
+        if self.wrap_around == wrap_around {
+            return Ok(());
+        }
+        self.wrap_around = wrap_around;
+        if self.is_init { self.print_yard() } else { Ok(()) }
+    }
+
+    /// dump the recorded match if recording, nothing happens otherwise
+    pub fn save_recording(&self, path: &str) -> std::io::Result<()> {
+        match &self.recorder {
+            Some(r) => r.save(path),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for TUIHelper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Frontend for TUIHelper {
+    fn draw_yard(&mut self, buf: YardBuf) -> Result<()> {
+        self.refresh_yard(buf)
+    }
+
+    fn draw_board(&mut self, board: &BoardType) -> Result<()> {
+        self.print_board(board)
+    }
+
+    fn show_info(&mut self, info: &str) -> Result<()> {
+        self.print_info(info)
+    }
+
+    fn round_over(&mut self, board: &BoardType) -> Result<()> {
+        self.print_round_over(board)
+    }
+
+    fn game_over(&mut self, info: &str) -> Result<()> {
+        self.print_info(info)
+    }
+
+    fn set_wrap_around(&mut self, wrap_around: bool) -> Result<()> {
+        TUIHelper::set_wrap_around(self, wrap_around)
+    }
+
+    fn set_footer(&mut self, footer: &str) {
+        self.footer = footer.to_string();
+    }
+
+    fn set_hud(&mut self, hud: &str) {
+        self.hud = hud.to_string();
+    }
+
+    fn set_chat(&mut self, chat: &BoardType) -> Result<()> {
+        // with the title
+        let shown = if self.chat.is_empty() { 0 } else { self.chat.len() + 1 };
+        self.chat = chat.clone();
+        if self.is_init { self.print_chat(shown) } else { Ok(()) }
+    }
+
+    fn save_recording(&self, path: &str) -> std::io::Result<()> {
+        TUIHelper::save_recording(self, path)
+    }
+}
+
+/// the text of a block without colors
+pub fn block_text(block: &TUIBlock) -> &str {
+    if block.content == EMPTY && block.bg != Color::White {
+        BODY_TEXT
+    } else {
+        &block.content
+    }
+}
+
+/// the yard with its fences as plain text, one line each row
+pub fn yard_text(buf: &YardBuf, wrap_around: bool) -> String {
+    let fence = if wrap_around { FENCE_DASHED } else { FENCE };
+    let fence_row = fence.repeat(buf.first().map_or(0, |r| r.len()) + 2);
+    let mut text = format!("{}\n", fence_row);
+    for r in buf {
+        text.push_str(fence);
+        for c in r {
+            text.push_str(block_text(c));
+        }
+        text.push_str(fence);
+        text.push('\n');
+    }
+    text.push_str(&fence_row);
+    text.push('\n');
+    text
+}
+
+/// a frontend without a terminal, keeping every screen as a plain text frame
+/// handy for automated tests and snapshots of the game
+#[derive(Clone, Debug, Default)]
+pub struct HeadlessFrontend {
+    pub buf: YardBuf,
+    pub wrap_around: bool,
+    /// a note kept under the board
+    pub footer: String,
+    /// diagnostics kept under the footer
+    pub hud: String,
+    /// the chat panel kept beside the board
+    pub chat: BoardType,
+    /// what is shown under the yard
+    pub below: String,
+    /// every screen shown, in order
+    pub frames: Vec<String>,
+}
+
+impl HeadlessFrontend {
+    pub fn new() -> HeadlessFrontend {
+        HeadlessFrontend::default()
+    }
+
+    /// the screen now, the yard and what is under it, the chat panel last
+    pub fn screen(&self) -> String {
+        let mut screen = format!("{}{}", yard_text(&self.buf, self.wrap_around), self.below);
+        if !self.chat.is_empty() {
+            screen.push_str(&HeadlessFrontend::board_text("\nChat", &self.chat));
+        }
+        screen
+    }
+
+    pub fn last_frame(&self) -> Option<&str> {
+        self.frames.last().map(String::as_str)
+    }
+
+    fn push_frame(&mut self) {
+        self.frames.push(self.screen());
+    }
+
+    fn board_text(title: &str, board: &BoardType) -> String {
+        let mut text = format!("{}\n", title);
         for line in board {
-            stdout()
-                .execute(SetForegroundColor(Color::White))?
-                .execute(SetBackgroundColor(line.0))?
-                .execute(Print(&line.1))?
-                .execute(ResetColor)?;
+            text.push_str(&line.1);
+        }
+        text
+    }
+
+    /// write the frames one after another, each beginning with a numbered separator
+    pub fn write_to<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        for (i, frame) in self.frames.iter().enumerate() {
+            writeln!(w, "--- frame {} ---", i)?;
+            writeln!(w, "{}", frame)?;
+        }
+        w.flush()
+    }
+}
+
+impl Frontend for HeadlessFrontend {
+    fn draw_yard(&mut self, buf: YardBuf) -> Result<()> {
+        self.buf = buf;
+        self.push_frame();
+        Ok(())
+    }
+
+    fn draw_board(&mut self, board: &BoardType) -> Result<()> {
+        self.below = HeadlessFrontend::board_text("Board", board);
+        self.below.push_str(&self.footer);
+        if !self.hud.is_empty() {
+            self.below.push('\n');
+            self.below.push_str(&self.hud);
         }
+        self.push_frame();
+        Ok(())
+    }
+
+    fn show_info(&mut self, info: &str) -> Result<()> {
+        self.below = info.to_string();
+        self.push_frame();
+        Ok(())
+    }
+
+    fn round_over(&mut self, board: &BoardType) -> Result<()> {
+        self.below = HeadlessFrontend::board_text("Round over! Next round is coming soon", board);
+        self.push_frame();
+        Ok(())
+    }
+
+    fn game_over(&mut self, info: &str) -> Result<()> {
+        self.show_info(info)
+    }
+
+    fn set_wrap_around(&mut self, wrap_around: bool) -> Result<()> {
+        self.wrap_around = wrap_around;
         Ok(())
     }
+
+    fn set_footer(&mut self, footer: &str) {
+        self.footer = footer.to_string();
+    }
+
+    fn set_hud(&mut self, hud: &str) {
+        self.hud = hud.to_string();
+    }
+
+    fn set_chat(&mut self, chat: &BoardType) -> Result<()> {
+        self.chat = chat.clone();
+        self.push_frame();
+        Ok(())
+    }
+
+    /// the frames as text, see `write_to`
+    fn save_recording(&self, path: &str) -> std::io::Result<()> {
+        self.write_to(&mut File::create(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(width: usize, height: usize) -> YardBuf {
+        let empty = TUIBlock { fg: Color::White, bg: Color::White, content: EMPTY.to_string() };
+        vec![vec![empty; width]; height]
+    }
+
+    #[test]
+    fn headless_frames_the_yard_in_its_fence() {
+        let mut ui = HeadlessFrontend::new();
+        let mut buf = field(3, 2);
+        buf[1][2].content = BEAN.to_string();
+        ui.draw_yard(buf).unwrap();
+        let fence = FENCE.repeat(5);
+        let expected = format!("{0}\n[]{1}[]\n[]{2}{3}[]\n{0}\n", fence, EMPTY.repeat(3), EMPTY.repeat(2), BEAN);
+        assert_eq!(ui.last_frame(), Some(expected.as_str()));
+
+        ui.set_wrap_around(true).unwrap();
+        ui.draw_yard(field(3, 2)).unwrap();
+        assert!(ui.last_frame().unwrap().starts_with(&FENCE_DASHED.repeat(5)));
+        assert_eq!(ui.frames.len(), 2);
+    }
+
+    #[test]
+    fn headless_keeps_what_is_under_the_yard() {
+        let mut ui = HeadlessFrontend::new();
+        ui.draw_yard(field(5, 5)).unwrap();
+        ui.show_info("Waiting for players").unwrap();
+        assert!(ui.last_frame().unwrap().ends_with("Waiting for players"));
+        ui.set_footer("footer");
+        ui.draw_board(&vec![(Color::Red, String::from("Ann: 3\n"))]).unwrap();
+        assert!(ui.last_frame().unwrap().ends_with("Board\nAnn: 3\nfooter"));
+
+        let mut out = Vec::new();
+        ui.write_to(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("--- frame 0 ---\n"));
+        assert!(out.contains("--- frame 2 ---\n"));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,73 @@
+//! pub mod scheduler: ticking at a steady rate
+//! the next tick is due a fixed interval after the last one was due, not after it ended,
+//! so the time spent simulating doesn't slow the game down
+
+use std::thread;
+use std::time::{ Duration, Instant };
+
+/// falling behind more than this many ticks, the scheduler catches up at once instead of bursting
+pub const MAX_LAG_TICKS: u32 = 5;
+
+pub struct Scheduler {
+    next: Instant,
+    interval: Duration,
+}
+
+impl Scheduler {
+    /// `rate`: ticks per second
+    pub fn new(rate: f64) -> Scheduler {
+        let interval = Duration::from_secs_f64(1.0 / rate);
+        Scheduler { next: Instant::now() + interval, interval }
+    }
+
+    /// change the rate, taking effect from the tick after the next
+    pub fn set_rate(&mut self, rate: f64) {
+        self.interval = Duration::from_secs_f64(1.0 / rate);
+    }
+
+    pub fn rate(&self) -> f64 {
+        1.0 / self.interval.as_secs_f64()
+    }
+
+    /// time between two ticks now
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// sleep till the next tick is due
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if self.next > now {
+            thread::sleep(self.next - now);
+            self.next += self.interval;
+        } else if now - self.next > self.interval * MAX_LAG_TICKS {
+            self.next = now + self.interval;
+        } else {
+            self.next += self.interval;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_and_interval_agree() {
+        let mut scheduler = Scheduler::new(10.0);
+        assert_eq!(scheduler.interval(), Duration::from_millis(100));
+        scheduler.set_rate(4.0);
+        assert_eq!(scheduler.interval(), Duration::from_millis(250));
+        assert!((scheduler.rate() - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wait_keeps_the_pace() {
+        let mut scheduler = Scheduler::new(100.0);
+        let start = Instant::now();
+        for _ in 0..5 {
+            scheduler.wait();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
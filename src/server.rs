@@ -1,71 +1,464 @@
 /// pub mod server: have a fn that can be started as a thread
 /// simulates the game, shall be wrapped before the user
 
-use crate::yard::{ self, YardSim, YardBuf, Direction };
+use crate::yard::{ self, YardSim, YardBuf, YardState, Direction, GameConfig, MatchMode, SpeedUp, MIN_TICK_RATE, MAX_TICK_RATE };
 use crate::render::Color;
+use crate::bot::{ self, Bot };
+use crate::transport::ServerTransport;
+use crate::scheduler::Scheduler;
+use crate::names::{ self, RenameReason };
+use crate::records::{ Records, GameStats, LEADERBOARD_LEN };
 
-use std::thread;
-use std::time::Duration;
-use std::sync::mpsc::{ Sender, Receiver, TryRecvError };
-use std::collections::HashMap;
+use std::time::{ Duration, Instant };
+use std::collections::{ HashMap, VecDeque };
+use std::path::{ Path, PathBuf };
+
+use rand::{ thread_rng, Rng };
 
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum YardCtrl {
     /// send a random client identifier and name for the server to shake hands
-    NewSnake(u64, String),
+    /// with the team wanted, `None` to be assigned one when teams are played
+    NewSnake(u64, String, Option<u8>),
     CtrlSnake(u64, Direction),
+    /// the player trades some length for a burst of speed
+    Boost(u64),
+    /// ask for a `Pong` with the same number, to measure the round trip
+    Ping(u64),
+    /// the player says something to everyone
+    Chat(u64, String),
+    /// the player leaves the game, its snake is removed and it won't respawn
+    LeaveGame(u64),
     /// when in singleplayer, it is processed by backend, and it quits
     /// when in multiplayer, it shall be intercepted avoiding backend quit
     QuitGame,
 }
 
+impl YardCtrl {
+    /// the player the control speaks for, `None` for those about the connection itself
+    pub fn rid(&self) -> Option<u64> {
+        match self {
+            YardCtrl::NewSnake(rid, _, _) | YardCtrl::CtrlSnake(rid, _) | YardCtrl::Boost(rid)
+                | YardCtrl::Chat(rid, _) | YardCtrl::LeaveGame(rid) => Some(*rid),
+            YardCtrl::Ping(_) | YardCtrl::QuitGame => None,
+        }
+    }
+}
+
 pub type BoardType = Vec<(Color, String)>; // board can be rendered with color
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum YardInfo {
     // send back the handle for the client, and request status
     RegisteredSnake(u64, bool),
+    /// the options of the game, sent before registering so clients can prepare
+    Config(GameConfig),
     RefreshScreen(YardBuf),
     Failed(u64),
     Board(BoardType),
+    /// seconds left before the round starts
+    Countdown(u64),
+    /// the final scoreboard of a round, the winner first
+    RoundOver(BoardType),
+    /// the player's snake is put on the field with the field id
+    Spawned(u64, u8),
+    /// what is on the field, for bots and whoever reasons about the game
+    State(YardState),
+    /// the answer to a `Ping`
+    Pong(u64),
+    /// someone said something, with the color and name of the speaker
+    Chat(Color, String, String),
+    /// the player said too much in a short while, the message is not passed on
+    ChatRefused(u64),
+    /// the player can't join, with the reason
+    Refused(u64, String),
+    /// the name wanted was taken or not allowed, the player goes by this one
+    Renamed(u64, String, RenameReason),
+    /// how the player did in the game just ended, and on this host so far if it keeps records
+    Summary(u64, BoardType),
+    /// the best players on this host, after a game ended
+    Leaderboard(BoardType),
+}
+
+/// the game gets at most this many times faster
+pub const MAX_SPEED_UP: f64 = 3.0;
+/// faster by this for each block the longest snake grows
+pub const GROWTH_SPEED_UP: f64 = 0.05;
+/// faster by this every `TIME_SPEED_UP_SECS` of a round
+pub const TIME_SPEED_UP: f64 = 0.1;
+pub const TIME_SPEED_UP_SECS: u64 = 10;
+/// seconds to get ready before a round
+pub const COUNTDOWN_SECS: u64 = 3;
+/// seconds the scoreboard stays before the next round
+pub const ROUND_BREAK_SECS: u64 = 5;
+/// chat messages are cut to this many characters
+pub const MAX_CHAT_LEN: usize = 80;
+/// a player says at most `CHAT_BURST` messages in `CHAT_WINDOW`
+pub const CHAT_BURST: usize = 3;
+pub const CHAT_WINDOW: Duration = Duration::from_secs(5);
+
+/// what the server is doing in a match, with ticks left or time spent
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Phase {
+    Countdown(u64),
+    Playing(Duration),
+    Break(u64),
+}
+
+/// how many times faster than the configured rate the game is
+///  - `growth`: blocks the longest snake has grown
+///  - `round_time`: time spent playing this round
+fn speed_factor(speed_up: SpeedUp, growth: usize, round_time: Duration) -> f64 {
+    let factor = match speed_up {
+        SpeedUp::Steady => 1.0,
+        SpeedUp::Growth => 1.0 + GROWTH_SPEED_UP * growth as f64,
+        SpeedUp::Time => 1.0 + TIME_SPEED_UP * (round_time.as_secs() / TIME_SPEED_UP_SECS) as f64,
+    };
+    factor.min(MAX_SPEED_UP)
+}
+
+
+/// send info to the clients, return from the server if no one is listening
+macro_rules! send_info {
+    ($transport: expr, $info: expr) => {
+        match $transport.send_info($info) {
+            Ok(_) => {}, Err(_) => { return; },
+        }
+    }
+}
+
+/// someone in the game, alive or waiting for the next round
+struct Player {
+    rid: u64,
+    name: String,
+    team: Option<u8>,
+    /// computer controlled if any
+    bot: Option<Box<dyn Bot>>,
+    /// when the recent chat messages were said, for the rate limit
+    chats: VecDeque<Instant>,
+    /// how the game goes, while the player has a snake in it
+    game: Option<GameStats>,
+}
+
+/// a chat message fit to be shown, without control characters and not too long
+/// `None` if nothing is left
+pub fn sanitize_chat(text: &str) -> Option<String> {
+    let text: String = text.chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .chars()
+        .take(MAX_CHAT_LEN)
+        .collect();
+    if text.is_empty() { None } else { Some(text) }
+}
+
+/// whether the player may say something now, and remember it if so
+fn allow_chat(p: &mut Player) -> bool {
+    let now = Instant::now();
+    while p.chats.front().is_some_and(|t| now.duration_since(*t) > CHAT_WINDOW) {
+        p.chats.pop_front();
+    }
+    if p.chats.len() >= CHAT_BURST {
+        return false;
+    }
+    p.chats.push_back(now);
+    true
+}
+
+/// put a snake of the player on the field, return the field id
+fn spawn(
+        y: &mut YardSim,
+        field_id: &mut HashMap<u64, u8>,
+        client_id: &mut [Option<u64>],
+        rid: u64,
+        team: Option<u8>,
+    ) -> Option<u8> {
+    let id = y.init_snake()?;
+    field_id.insert(rid, id);
+    client_id[id as usize] = Some(rid);
+    y.set_team(id, team);
+    Some(id)
+}
+
+/// the name of a player who is in the game
+fn name_of(players: &[Player], rid: u64) -> &str {
+    match players.iter().find(|p| p.rid == rid) {
+        Some(p) => &p.name,
+        None => "?",
+    }
+}
+
+/// the team a newcomer plays for, the wanted one if possible, otherwise the smallest
+fn assign_team(config: &GameConfig, players: &[Player], wanted: Option<u8>) -> Option<u8> {
+    let teams = config.teams.min(yard::MAX_TEAMS);
+    if teams < 2 {
+        return None;
+    }
+    match wanted {
+        Some(t) if t < teams => Some(t),
+        _ => (0..teams).min_by_key(|t| players.iter().filter(|p| p.team == Some(*t)).count()),
+    }
+}
+
+/// a team, or a player on its own, who competes in a round
+fn side_of(p: &Player) -> (Option<u8>, u64) {
+    match p.team {
+        Some(t) => (Some(t), 0),
+        None => (None, p.rid),
+    }
+}
+
+/// the different sides among the players
+fn sides_of<'a>(players: impl Iterator<Item = &'a Player>) -> Vec<(Option<u8>, u64)> {
+    let mut sides: Vec<(Option<u8>, u64)> = players.map(side_of).collect();
+    sides.sort();
+    sides.dedup();
+    sides
+}
+
+/// add up the scores of each team, highest first
+fn team_scores(players: &[Player], round_score: &HashMap<u64, usize>) -> Vec<(u8, usize)> {
+    let mut totals = Vec::<(u8, usize)>::new();
+    for p in players {
+        if let Some(t) = p.team {
+            let score = *round_score.get(&p.rid).unwrap_or(&0);
+            match totals.iter_mut().find(|total| total.0 == t) {
+                Some(total) => { total.1 += score; },
+                None => { totals.push((t, score)); },
+            }
+        }
+    }
+    totals.sort_by_key(|total| std::cmp::Reverse(total.1));
+    totals
+}
+
+/// whether the round is over after this tick, give the scoreboard if so
+///  - `round_time`: time spent playing this round
+///  - `alive`: client ids of living snakes
+///  - `round_score`: scores of every player in this round
+fn round_result(
+        mode: MatchMode,
+        round_time: Duration,
+        alive: &[u64],
+        players: &[Player],
+        round_score: &HashMap<u64, usize>,
+    ) -> Option<BoardType> {
+    let alive_sides = sides_of(players.iter().filter(|p| alive.contains(&p.rid)));
+    let teams = team_scores(players, round_score);
+    let over = match mode {
+        MatchMode::Endless => { return None; },
+        MatchMode::TimeLimit(secs) => round_time >= Duration::from_secs(secs),
+        // in team mode only the totals count, a member's own score never wins the round
+        MatchMode::ScoreTarget(target) if !teams.is_empty() => teams.iter().any(|t| t.1 >= target),
+        MatchMode::ScoreTarget(target) => round_score.values().any(|s| *s >= target),
+        MatchMode::LastStanding
+            => alive_sides.len() <= if sides_of(players.iter()).len() > 1 { 1 } else { 0 },
+    } || alive.is_empty();
+    if !over {
+        return None;
+    }
+    // the survivors win the last standing, otherwise the top scorers
+    let survivor = match alive_sides[..] {
+        [side] if mode == MatchMode::LastStanding => Some(side),
+        _ => None,
+    };
+    let mut board = BoardType::new();
+    let mut teams = teams;
+    if let Some((Some(t), _)) = survivor {
+        teams.sort_by_key(|total| total.0 != t);
+    }
+    for (place, (t, score)) in teams.iter().enumerate() {
+        board.push((
+            yard::TEAM_COLOR_MAP[*t as usize][0],
+            format!(
+                "{}Team {}: {}\n",
+                if place == 0 { "Winner: " } else { "" }, yard::TEAM_NAMES[*t as usize], score,
+            ),
+        ));
+    }
+    let mut ranking: Vec<(u64, usize)> = players.iter()
+        .map(|p| (p.rid, *round_score.get(&p.rid).unwrap_or(&0)))
+        .collect();
+    ranking.sort_by_key(|r| std::cmp::Reverse(r.1));
+    if let Some((None, rid)) = survivor {
+        ranking.sort_by_key(|r| r.0 != rid);
+    }
+    for (place, (rid, score)) in ranking.iter().enumerate() {
+        board.push((
+            if place == 0 && teams.is_empty() { Color::DarkYellow } else { Color::DarkGrey },
+            format!("{}. {}: {}\n", place + 1, name_of(players, *rid), score),
+        ));
+    }
+    Some(board)
+}
+
+/// the records kept by the server, and where
+type Recorder = Option<(PathBuf, Records)>;
+
+/// a game ended for the players, count it in the records if any are kept
+/// gives the summary of each, followed by the leaderboard, to be sent
+///  - `finished`: the players whose game ended, bots are not counted
+fn end_game(recorder: &mut Recorder, mode: MatchMode, finished: Vec<(u64, String, GameStats)>) -> Vec<YardInfo> {
+    if finished.is_empty() {
+        return Vec::new();
+    }
+    let (path, records) = match recorder {
+        Some((path, records)) => (path, records),
+        None => {
+            return finished.into_iter()
+                .map(|(rid, name, game)| YardInfo::Summary(rid, vec![(Color::Black, format!("{}: {}\n", name, game.describe()))]))
+                .collect();
+        },
+    };
+    let high_scores: Vec<usize> = finished.iter()
+        .map(|f| records.player(&f.1).map_or(0, |p| p.high_score))
+        .collect();
+    records.record(&format!("{:?}", mode), finished.iter().map(|f| (f.1.clone(), f.2.clone())).collect());
+    if let Err(e) = records.save_to(path) {
+        println!("Failed to save the records {}", e);
+    }
+    let mut infos: Vec<YardInfo> = finished.iter().zip(high_scores).map(|((rid, name, game), high_score)| {
+        let mut summary = vec![(Color::Black, format!(
+            "{}: {}{}\n", name, game.describe(), if game.score > high_score { ", a new high score!" } else { "" },
+        ))];
+        if let Some(p) = records.player(name) {
+            summary.push((Color::Black, format!(
+                "{}'s best: {} points, {} kills in {} games, {} blocks long\n",
+                name, p.high_score, p.kills, p.games, p.longest,
+            )));
+        }
+        YardInfo::Summary(*rid, summary)
+    }).collect();
+    infos.push(YardInfo::Leaderboard(records.leaderboard(LEADERBOARD_LEN)));
+    infos
+}
+
+/// the players' games in progress, taken out as they are over, bots are left out
+fn take_games<'a>(players: impl Iterator<Item = &'a mut Player>) -> Vec<(u64, String, GameStats)> {
+    players
+        .filter(|p| p.bot.is_none())
+        .filter_map(|p| p.game.take().map(|g| (p.rid, p.name.clone(), g)))
+        .collect()
 }
 
 /// simulating the yard in a seperate thread
-/// use channel to input/output control, info and buffer
-pub fn start_and_serve(
-        info_tx: Sender<YardInfo>,
-        ctrl_rx: Receiver<YardCtrl>,
-    ) {
+/// use the transport to input/output control, info and buffer
+pub fn start_and_serve(config: GameConfig, transport: &dyn ServerTransport) {
+    serve(config, transport, None);
+}
+
+/// like `start_and_serve`, keeping the leaderboard and match history in the records file,
+/// see `records`, the game goes on without it if the file can't be understood
+pub fn start_and_serve_recorded(config: GameConfig, transport: &dyn ServerTransport, path: &Path) {
+    let recorder = match Records::load_from(path) {
+        Ok(records) => Some((path.to_path_buf(), records)),
+        Err(e) => {
+            println!("Games won't be recorded, {}", e);
+            None
+        },
+    };
+    serve(config, transport, recorder);
+}
+
+fn serve(config: GameConfig, transport: &dyn ServerTransport, mut recorder: Recorder) {
     // create a yard y and send the initial screen buffer
-    let mut y = YardSim::new(30, 20, 5, 3);
-    info_tx.send(YardInfo::RefreshScreen(y.generate_buf())).unwrap();
+    let mut y = YardSim::from_config(&config);
+    transport.send_info(YardInfo::RefreshScreen(y.generate_buf())).unwrap();
     let mut field_id = HashMap::new();  // client id to field id
-    // field id to client id & name
+    // field id to client id
     let mut client_id = [None; yard::MAX_PLAYERS as usize];
-    let mut client_name = [None, None, None, None, None]; // again
+    // everyone in the game, alive or waiting for the next round, by joining order
+    let mut players: Vec<Player> = Vec::new();
+    let mut round_score: HashMap<u64, usize> = HashMap::new();
+    let rounds = config.match_mode != MatchMode::Endless;
+    let tick_rate = config.tick_rate.clamp(MIN_TICK_RATE, MAX_TICK_RATE);
+    let mut scheduler = Scheduler::new(tick_rate as f64);
+    let mut phase = if rounds {
+        Phase::Countdown(COUNTDOWN_SECS * tick_rate)
+    } else {
+        Phase::Playing(Duration::ZERO)
+    };
+    // bots join first, and give way to people later
+    for i in 0..config.bots.min(yard::MAX_PLAYERS) {
+        let rid = thread_rng().gen();
+        let team = assign_team(&config, &players, None);
+        players.push(Player {
+            rid,
+            name: format!("Bot {} ({})", i + 1, config.bot_level.name()),
+            team,
+            bot: Some(bot::new_bot(config.bot_level)),
+            chats: VecDeque::new(),
+            game: None,
+        });
+        if let Some(id) = spawn(&mut y, &mut field_id, &mut client_id, rid, team) {
+            send_info!(transport, YardInfo::Spawned(rid, id));
+        }
+    }
     loop {
-        thread::sleep(Duration::from_millis(100));
+        scheduler.wait();
         // receiving control signals
         loop {
-            match ctrl_rx.try_recv() {
-                Ok(YardCtrl::NewSnake(rid, name)) => { // register snake
-                    match y.init_snake() {
-                        Some(id) => {
-                            field_id.insert(rid, id);
-                            client_id[id as usize] = Some(rid);
-                            client_name[id as usize] = Some(name);
-                            match info_tx.send(YardInfo::RegisteredSnake(rid, true)) {
-                                Ok(_) => {}, Err(_) => { return; },
-                            };
-                        },
+            match transport.try_recv_ctrl() {
+                Ok(Some(YardCtrl::NewSnake(rid, wanted_name, wanted))) => { // register snake
+                    if players.iter().any(|p| p.rid == rid) {
+                        send_info!(transport, YardInfo::Refused(rid, String::from("the id is taken")));
+                        continue;
+                    }
+                    let sanitized = match names::sanitize_name(&wanted_name) {
+                        Some(n) => n,
                         None => {
-
+                            send_info!(transport, YardInfo::Refused(
+                                rid, format!("the name has no letters, digits or {}", names::NAME_PUNCTUATION),
+                            ));
+                            continue;
+                        },
+                    };
+                    let name = names::unique_name(&sanitized, players.iter().map(|p| p.name.as_str()));
+                    if players.len() >= yard::MAX_PLAYERS as usize {
+                        // the last bot leaves for the newcomer
+                        match players.iter().rposition(|p| p.bot.is_some()) {
+                            Some(i) => {
+                                let bot_rid = players.remove(i).rid;
+                                if let Some(id) = field_id.remove(&bot_rid) {
+                                    y.remove_snake(id);
+                                    client_id[id as usize] = None;
+                                }
+                            },
+                            None => {
+                                send_info!(transport, YardInfo::Refused(rid, String::from("the game is full")));
+                                continue;
+                            },
+                        }
+                    }
+                    let team = assign_team(&config, &players, wanted);
+                    // joining in the middle of a round waits for the next one
+                    let game = match phase {
+                        Phase::Playing(_) if rounds => None,
+                        Phase::Break(_) => None,
+                        _ => {
+                            match spawn(&mut y, &mut field_id, &mut client_id, rid, team) {
+                                Some(id) => { send_info!(transport, YardInfo::Spawned(rid, id)); },
+                                None => {
+                                    send_info!(transport, YardInfo::Refused(rid, String::from("no room on the field")));
+                                    continue;
+                                },
+                            }
+                            Some(GameStats::default())
                         },
+                    };
+                    players.push(Player { rid, name: name.clone(), team, bot: None, chats: VecDeque::new(), game });
+                    send_info!(transport, YardInfo::Config(config.clone()));
+                    send_info!(transport, YardInfo::RegisteredSnake(rid, true));
+                    if name != sanitized {
+                        send_info!(transport, YardInfo::Renamed(rid, name, RenameReason::Taken));
+                    } else if name != wanted_name.trim() {
+                        send_info!(transport, YardInfo::Renamed(rid, name, RenameReason::Sanitized));
                     }
                 },
-                Ok(YardCtrl::CtrlSnake(id, d)) => {
+                Ok(Some(YardCtrl::CtrlSnake(id, d))) => {
                     match field_id.get(&id) {
                         Some(i) => { y.control_snake(*i, d); },
                         None => { 
@@ -74,36 +467,191 @@ pub fn start_and_serve(
                         },
                     }
                 },
-                Ok(YardCtrl::QuitGame) => {
+                Ok(Some(YardCtrl::Boost(id))) => {
+                    if let Some(i) = field_id.get(&id) {
+                        y.boost_snake(*i);
+                    }
+                },
+                // only the channel transport passes pings on, its one client is the sender,
+                // the network ones answer them on the connection they came from
+                Ok(Some(YardCtrl::Ping(n))) => {
+                    send_info!(transport, YardInfo::Pong(n));
+                },
+                Ok(Some(YardCtrl::Chat(rid, text))) => {
+                    // the network transports only pass on a player's own chats, see `transport::Claims`
+                    let (p, text) = match (players.iter_mut().find(|p| p.rid == rid), sanitize_chat(&text)) {
+                        (Some(p), Some(text)) => (p, text),
+                        _ => { continue; },
+                    };
+                    if !allow_chat(p) {
+                        send_info!(transport, YardInfo::ChatRefused(rid));
+                        continue;
+                    }
+                    let color = match (field_id.get(&rid), p.team) {
+                        (Some(id), _) => y.color_of(*id),
+                        (None, Some(t)) => yard::TEAM_COLOR_MAP[t as usize][0],
+                        (None, None) => Color::Black,
+                    };
+                    send_info!(transport, YardInfo::Chat(color, p.name.clone(), text));
+                },
+                Ok(Some(YardCtrl::LeaveGame(rid))) => {
+                    // the game left counts as well
+                    let left = take_games(players.iter_mut().filter(|p| p.rid == rid));
+                    for info in end_game(&mut recorder, config.match_mode, left) {
+                        send_info!(transport, info);
+                    }
+                    players.retain(|p| p.rid != rid);
+                    if let Some(i) = field_id.get(&rid) {
+                        y.kill_snake(*i);
+                    }
+                },
+                Ok(Some(YardCtrl::QuitGame)) => {
+                    end_game(&mut recorder, config.match_mode, take_games(players.iter_mut()));
                     return;
                 },
-                Err(TryRecvError::Empty) => { break; },
-                Err(TryRecvError::Disconnected) => { return; },
+                Ok(None) => { break; },
+                Err(_e) => { return; },
             };
         }
+        match phase {
+            Phase::Countdown(left) => {
+                if left % tick_rate == 0 && left > 0 {
+                    send_info!(transport, YardInfo::Countdown(left / tick_rate));
+                }
+                phase = if left == 0 { Phase::Playing(Duration::ZERO) } else { Phase::Countdown(left - 1) };
+                send_info!(transport, YardInfo::RefreshScreen(y.generate_buf()));
+                send_info!(transport, YardInfo::State(y.snapshot()));
+                continue;
+            },
+            Phase::Break(left) if left > 0 => {
+                phase = Phase::Break(left - 1);
+                continue;
+            },
+            Phase::Break(_) => {
+                // restart with everyone still in the game
+                y = YardSim::from_config(&config);
+                field_id.clear();
+                client_id = [None; yard::MAX_PLAYERS as usize];
+                round_score.clear();
+                for p in players.iter_mut() {
+                    if let Some(id) = spawn(&mut y, &mut field_id, &mut client_id, p.rid, p.team) {
+                        p.game = Some(GameStats::default());
+                        send_info!(transport, YardInfo::Spawned(p.rid, id));
+                    }
+                }
+                phase = Phase::Countdown(COUNTDOWN_SECS * tick_rate);
+                scheduler.set_rate(tick_rate as f64);
+                send_info!(transport, YardInfo::RefreshScreen(y.generate_buf()));
+                continue;
+            },
+            Phase::Playing(t) => {
+                phase = Phase::Playing(t + scheduler.interval());
+            },
+        }
+        let round_time = match phase { Phase::Playing(t) => t, _ => Duration::ZERO };
+        // bots think before the tick
+        let state = y.snapshot();
+        for p in players.iter_mut() {
+            if let (Some(b), Some(id)) = (&mut p.bot, field_id.get(&p.rid)) {
+                if let Some(d) = b.decide(&state, *id) {
+                    y.control_snake(*id, d);
+                }
+            }
+        }
         let (score, failed) = y.next_tick();
+        for (attacker, _victim) in y.kills() {
+            let rid = client_id[*attacker as usize];
+            if let Some(g) = players.iter_mut().find(|p| Some(p.rid) == rid).and_then(|p| p.game.as_mut()) {
+                g.kills += 1;
+            }
+        }
+        let after = y.snapshot();
+        for p in players.iter_mut() {
+            if let (Some(g), Some(id)) = (&mut p.game, field_id.get(&p.rid)) {
+                g.update(score[*id as usize], after.snake(*id).map_or(0, |s| s.body.len()));
+            }
+        }
+        let longest = state.snakes.iter().map(|s| s.body.len()).max().unwrap_or(0);
+        let growth = longest.saturating_sub(config.init_snake_len);
+        scheduler.set_rate(tick_rate as f64 * speed_factor(config.speed_up, growth, round_time));
         let mut board = BoardType::new();
+        match config.match_mode {
+            MatchMode::TimeLimit(secs) => {
+                let left = Duration::from_secs(secs).saturating_sub(round_time).as_secs_f64().ceil();
+                board.push((Color::Black, format!("Time left: {}s\n", left)));
+            },
+            MatchMode::ScoreTarget(target) => {
+                board.push((Color::Black, format!("First to {} points\n", target)));
+            },
+            MatchMode::LastStanding => {
+                board.push((Color::Black, "Last snake standing\n".to_string()));
+            },
+            MatchMode::Endless => {},
+        }
+        let mut alive = Vec::new();
+        let mut lines = BoardType::new();
         for i in 0..yard::MAX_PLAYERS as usize {
             if score[i] > 0 { // there is a snake i
-                board.push((
-                    yard::PLAYER_COLOR_MAP[i],
-                    format!("{}: {}\n", client_name[i].as_ref().unwrap(), score[i]),
+                let rid = client_id[i].unwrap();
+                lines.push((
+                    y.color_of(i as u8),
+                    format!(
+                        "{}: {}{}\n",
+                        name_of(&players, rid), score[i], y.effects(i as u8).describe(),
+                    ),
                 ));
+                round_score.insert(rid, score[i]);
+                if !failed[i] {
+                    alive.push(rid);
+                }
             }
             if failed[i] {
-                match info_tx.send(YardInfo::Failed(client_id[i].unwrap())) {
-                    Ok(_) => {}, Err(_) => { return; },
-                };
-                field_id.remove(&client_id[i].unwrap());
+                let rid = client_id[i].unwrap();
+                // the game is over at the death in the endless game, at the end of the round otherwise
+                if !rounds {
+                    let over = take_games(players.iter_mut().filter(|p| p.rid == rid));
+                    for info in end_game(&mut recorder, config.match_mode, over) {
+                        send_info!(transport, info);
+                    }
+                }
+                send_info!(transport, YardInfo::Failed(rid));
+                field_id.remove(&rid);
                 client_id[i] = None;
-                client_name[i] = None;
+                if !rounds {
+                    players.retain(|p| p.rid != rid || p.bot.is_some());
+                }
             }
         }
-        match info_tx.send(YardInfo::Board(board)) {
-            Ok(_) => {}, Err(_) => { return; },
-        };
-        match info_tx.send(YardInfo::RefreshScreen(y.generate_buf())) {
-            Ok(_) => {}, Err(_) => { return; },
-        };
+        // bots never give up in the endless game
+        if !rounds {
+            for p in players.iter().filter(|p| p.bot.is_some()) {
+                if field_id.contains_key(&p.rid) {
+                    continue;
+                }
+                if let Some(id) = spawn(&mut y, &mut field_id, &mut client_id, p.rid, p.team) {
+                    send_info!(transport, YardInfo::Spawned(p.rid, id));
+                }
+            }
+        }
+        // teams go before their members
+        for (t, total) in team_scores(&players, &round_score) {
+            board.push((
+                yard::TEAM_COLOR_MAP[t as usize][0],
+                format!("Team {}: {}\n", yard::TEAM_NAMES[t as usize], total),
+            ));
+        }
+        board.append(&mut lines);
+        send_info!(transport, YardInfo::Board(board));
+        send_info!(transport, YardInfo::RefreshScreen(y.generate_buf()));
+        send_info!(transport, YardInfo::State(y.snapshot()));
+        if let Some(scoreboard)
+            = round_result(config.match_mode, round_time, &alive, &players, &round_score) {
+            for info in end_game(&mut recorder, config.match_mode, take_games(players.iter_mut())) {
+                send_info!(transport, info);
+            }
+            send_info!(transport, YardInfo::RoundOver(scoreboard));
+            phase = Phase::Break(ROUND_BREAK_SECS * tick_rate);
+            scheduler.set_rate(tick_rate as f64);
+        }
     }
 }
\ No newline at end of file
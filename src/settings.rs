@@ -0,0 +1,134 @@
+//! pub mod settings: what the player chose, kept between launches
+//! the settings are a JSON file `socket_snake/settings.json` in the XDG config directory,
+//! `$XDG_CONFIG_HOME` or else `~/.config`, written whenever something is changed in the menu
+//! anything missing in the file takes the default, so the file may be edited by hand
+//! a file that can't be loaded is moved aside to `settings.json.bak` rather than written over
+
+use crate::keys::{ self, KeyBindings };
+use crate::names;
+use crate::render::Theme;
+use crate::yard::GameConfig;
+
+use serde::{ Deserialize, Serialize };
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+pub const SETTINGS_DIR: &str = "socket_snake";
+pub const SETTINGS_FILE: &str = "settings.json";
+pub const SETTINGS_BACKUP: &str = "settings.json.bak";
+/// servers joined lately are remembered, the latest first
+pub const MAX_RECENT_SERVERS: usize = 5;
+
+/// reasons why the settings can't be loaded or saved
+#[derive(Debug)]
+pub enum SettingsError {
+    /// neither `$XDG_CONFIG_HOME` nor `$HOME` is set
+    NoConfigDir,
+    Io(std::io::Error),
+    BadFile(serde_json::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::NoConfigDir
+                => write!(f, "there is no config directory, set $XDG_CONFIG_HOME or $HOME"),
+            SettingsError::Io(e)
+                => write!(f, "can't read or write the settings: {}", e),
+            SettingsError::BadFile(e)
+                => write!(f, "the settings file is not understood: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+/// the player's profile and preferences
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// empty until the player is given one
+    pub name: String,
+    /// servers joined lately, the latest first
+    pub servers: Vec<String>,
+    pub keys: KeyBindings,
+    /// the keys to navigate and boost of each seat when sharing the keyboard, the player's own first
+    pub shared_keys: Vec<KeyBindings>,
+    pub theme: Theme,
+    /// the game settings offered when playing alone or hosting
+    pub game: GameConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            name: String::new(),
+            servers: Vec::new(),
+            keys: KeyBindings::default(),
+            shared_keys: keys::shared_presets(),
+            theme: Theme::default(),
+            game: GameConfig::default(),
+        }
+    }
+}
+
+/// the game's directory in an XDG base directory, such as `$XDG_CONFIG_HOME/socket_snake`
+///  - `var`: the variable naming the base directory
+///  - `fallback`: the base directory under `$HOME` if the variable is not set
+pub fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    let base = env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute()) // relative ones are to be ignored by the XDG spec
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(fallback)))?;
+    Some(base.join(SETTINGS_DIR))
+}
+
+/// `settings.json` in the XDG config directory
+pub fn settings_path() -> Option<PathBuf> {
+    Some(xdg_dir("XDG_CONFIG_HOME", ".config")?.join(SETTINGS_FILE))
+}
+
+impl Settings {
+    /// the settings saved, the defaults if there are none yet
+    pub fn load() -> Result<Settings, SettingsError> {
+        let path = settings_path().ok_or(SettingsError::NoConfigDir)?;
+        let text = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => { return Ok(Settings::default()); },
+            Err(e) => { return Err(SettingsError::Io(e)); },
+        };
+        let mut settings: Settings = serde_json::from_str(&text).map_err(SettingsError::BadFile)?;
+        // a name edited by hand is taken only if allowed
+        settings.name = names::validate_name(&settings.name).unwrap_or_default();
+        Ok(settings)
+    }
+
+    /// move the settings file aside, so the one that couldn't be loaded is not lost when saving
+    /// return where it went
+    pub fn back_up() -> Result<PathBuf, SettingsError> {
+        let path = settings_path().ok_or(SettingsError::NoConfigDir)?;
+        let backup = path.with_file_name(SETTINGS_BACKUP);
+        fs::rename(&path, &backup).map_err(SettingsError::Io)?;
+        Ok(backup)
+    }
+
+    /// write the settings, making the directory if needed
+    pub fn save(&self) -> Result<(), SettingsError> {
+        let path = settings_path().ok_or(SettingsError::NoConfigDir)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(SettingsError::Io)?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(SettingsError::BadFile)?;
+        fs::write(path, text).map_err(SettingsError::Io)
+    }
+
+    /// a server was joined, it goes first in the list
+    pub fn remember_server(&mut self, addr: &str) {
+        self.servers.retain(|s| s != addr);
+        self.servers.insert(0, addr.to_string());
+        self.servers.truncate(MAX_RECENT_SERVERS);
+    }
+}
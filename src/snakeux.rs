@@ -1,9 +1,22 @@
 /// pub mod snakeux: user experience before and after actual game rendering
 
 use crate::multiplayer;
+use crate::yard::{
+    GameConfig, MatchMode, BotLevel, BOT_LEVELS, SpeedUp, POWER_UPS, MAX_TEAMS, MAX_PLAYERS,
+    MIN_TICK_RATE, MAX_TICK_RATE,
+};
+use crate::map::YardMap;
+use crate::transport::Delivery;
+use crate::controller::{ ControllerChoice, ScriptedController };
+use crate::names;
+use crate::keys::{ self, KeyBindings, KeyError, PRESETS, SHARED_LAYOUTS, SHARED_ACTIONS };
+use crate::render::{ Theme, THEMES };
+use crate::settings::{ self, Settings };
+use crate::records::{ self, Records };
 
 use std::io::{ stdin, stdout, Error, ErrorKind };
 use std::net::Ipv4Addr;
+use std::time::{ SystemTime, UNIX_EPOCH };
 
 use rand::{ thread_rng };
 use rand::prelude::SliceRandom;
@@ -31,8 +44,12 @@ pub const MENU_HINT: &str = r#"
                     (1) Start singleplayer game
                     (2) Join a hosted game
                     (3) Host a game
-                    (4) Change your name
-                    (5) Exit
+                    (4) Toggle recording matches as asciicast
+                    (5) Change your name
+                    (6) Change who plays your snake
+                    (7) Settings
+                    (8) Leaderboard
+                    (9) Exit
 --------------------------------------------------------------------
 Please type in your option:
 "#;
@@ -40,12 +57,23 @@ Please type in your option:
 pub const HOST_HINT: &str = r#"
 --------------------------------------------------------------------
      Socket Snake host is running on LocalHost "#;
+pub const BOT_HINT: &str = r#"
+     Bots may connect with the JSON API on "#;
 pub const SEPERATOR: &str = r#"
                    Press Ctrl+C to End serving
 --------------------------------------------------------------------
 "#;
 
-pub const CHOICE_RANGE: std::ops::Range::<u8> = 1..6;
+pub const RECORDING_HINT: &str = r#"
+                    Matches will be recorded to the working directory"#;
+
+pub const CONTROLLER_HINT: &str = r#"
+                    Your snake is played by "#;
+
+pub const CHOICE_RANGE: std::ops::Range::<u8> = 1..10;
+/// the leaderboard screen shows this many players and this many of the latest games
+pub const LEADERBOARD_SHOWN: usize = 10;
+pub const HISTORY_SHOWN: usize = 5;
 pub const DEFAULT_NAMES: [&str; 9] = [
         "Happy Pants",
         "Mighty_Lord_Cobra",
@@ -59,10 +87,14 @@ pub const DEFAULT_NAMES: [&str; 9] = [
     ];
 
 pub enum UsersIdea {
-    Singleplayer,
-    JoinGame(String),   // join or host by binding ip
-    HostGame(String),
+    Singleplayer(GameConfig),
+    JoinGame(String, Option<u8>),   // join or host by binding ip, with the team wanted
+    HostGame(String, GameConfig, Delivery),
+    ToggleRecording,
     ChangeName,
+    ChangeController(ControllerChoice),
+    ChangeSettings,
+    ShowLeaderboard,
     ExitGame,
 }
 
@@ -91,19 +123,367 @@ pub fn input_ip_addr_port() -> String {
     }
 }
 
+/// let the user pick a server joined lately, or type in another
+pub fn input_server(recent: &[String]) -> String {
+    if recent.is_empty() {
+        return input_ip_addr_port();
+    }
+    println!("Please choose a server:");
+    for (i, s) in recent.iter().enumerate() {
+        println!("({}) {}", i + 1, s);
+    }
+    println!("({}) Another server", recent.len() + 1);
+    match input_number("Server", 1..=recent.len() as u64 + 1) as usize {
+        n if n <= recent.len() => recent[n - 1].clone(),
+        _ => input_ip_addr_port(),
+    }
+}
+
+/// ask a yes or no question, anything but yes means no
+pub fn input_yes_no(question: &str) -> bool {
+    println!("{} (y/N)", question);
+    let mut line = String::new();
+    stdin().read_line(&mut line).unwrap();
+    matches!(line.trim(), "y" | "Y" | "yes")
+}
+
+/// ask for a number in the range, until the user gets it right
+pub fn input_number(prompt: &str, range: std::ops::RangeInclusive<u64>) -> u64 {
+    println!("{} ({} to {})", prompt, range.start(), range.end());
+    loop {
+        let mut line = String::new();
+        stdin().read_line(&mut line).unwrap();
+        match line.trim().parse::<u64>() {
+            Ok(num) if range.contains(&num) => { break num; },
+            _ => { println!("Please type in a number ({} to {}):", range.start(), range.end()); },
+        }
+    }
+}
+
+/// let the user pick how the game is won
+pub fn input_match_mode() -> MatchMode {
+    println!("Please choose a match mode:");
+    println!("(1) Endless");
+    println!("(2) Time-limited rounds");
+    println!("(3) First to N points");
+    println!("(4) Last snake standing");
+    match input_number("Match mode", 1..=4) {
+        2 => MatchMode::TimeLimit(input_number("Seconds of each round", 10..=3600)),
+        3 => MatchMode::ScoreTarget(input_number("Points to win", 5..=1000) as usize),
+        4 => MatchMode::LastStanding,
+        _ => MatchMode::Endless,
+    }
+}
+
+/// let the user pick whether the game gets faster
+pub fn input_speed_up() -> SpeedUp {
+    println!("Please choose how the speed goes:");
+    println!("(1) Steady");
+    println!("(2) Faster as snakes grow");
+    println!("(3) Faster as the round goes on");
+    match input_number("Speed", 1..=3) {
+        2 => SpeedUp::Growth,
+        3 => SpeedUp::Time,
+        _ => SpeedUp::Steady,
+    }
+}
+
+/// let the user pick a built-in map or load one from a file, `None` for the open field
+pub fn input_map() -> Option<YardMap> {
+    let builtins = YardMap::builtins();
+    println!("Please choose a map:");
+    println!("(0) Open field");
+    for (i, m) in builtins.iter().enumerate() {
+        println!("({}) {}", i + 1, m.name);
+    }
+    println!("({}) Load a map file", builtins.len() + 1);
+    loop {
+        let mut line = String::new();
+        stdin().read_line(&mut line).unwrap();
+        match line.trim().parse::<usize>() {
+            Ok(0) => { return None; },
+            Ok(num) if num <= builtins.len() => { return Some(builtins[num - 1].clone()); },
+            Ok(num) if num == builtins.len() + 1 => {
+                println!("Please input the path of the map file:");
+                let mut path = String::new();
+                stdin().read_line(&mut path).unwrap();
+                match YardMap::load(path.trim()) {
+                    Ok(m) => { return Some(m); },
+                    Err(e) => { println!("Invalid map, {}. Please choose again:", e); },
+                }
+            },
+            _ => { println!("Please type in a number:"); },
+        }
+    }
+}
+
+/// let the user customize the game before starting it
+pub fn input_game_config() -> GameConfig {
+    let wrap_around = input_yes_no("Play in a wrap-around arena, where snakes pass through the fences?");
+    let map = input_map();
+    let power_ups = if input_yes_no("Enable power-ups besides beans?") {
+        POWER_UPS.to_vec()
+    } else {
+        Vec::new()
+    };
+    let match_mode = input_match_mode();
+    let teams = input_number("How many teams? 0 for free for all", 0..=MAX_TEAMS as u64) as u8;
+    let friendly_fire = teams < 2 || !input_yes_no("Can snakes pass through their teammates' bodies?");
+    let bots = input_number("How many bots? They make room for people joining", 0..=(MAX_PLAYERS - 1) as u64) as u8;
+    let bot_level = if bots > 0 { input_bot_level() } else { BotLevel::Normal };
+    let tick_rate = input_number("Ticks per second? 10 is the classic speed", MIN_TICK_RATE..=MAX_TICK_RATE);
+    let speed_up = input_speed_up();
+    GameConfig {
+        wrap_around, map, power_ups, match_mode, teams, friendly_fire, bots, bot_level,
+        tick_rate, speed_up,
+        ..GameConfig::default()
+    }
+}
+
+/// the usual game settings, or new ones that become the usual
+pub fn input_usual_game_config(usual: &mut GameConfig) -> GameConfig {
+    if !input_yes_no("Play with your usual game settings?") {
+        *usual = input_game_config();
+    }
+    usual.clone()
+}
+
+/// let the user pick a color theme
+pub fn input_theme() -> Theme {
+    println!("Please choose a color theme:");
+    for (i, theme) in THEMES.iter().enumerate() {
+        println!("({}) {}", i + 1, theme.name());
+    }
+    THEMES[input_number("Theme", 1..=THEMES.len() as u64) as usize - 1]
+}
+
+/// let the user change the settings kept between launches
+pub fn input_settings(settings: &mut Settings) {
+    match settings::settings_path() {
+        Some(path) => { println!("Settings are kept in {}", path.display()); },
+        None => { println!("Settings can't be kept, there is no config directory"); },
+    }
+    println!("Please choose what to change:");
+    println!("(1) Color theme, now {}", settings.theme.name());
+    println!("(2) Usual game settings");
+    println!("(3) Forget the servers joined");
+    println!("(4) Key bindings");
+    println!("(5) Keys for sharing the keyboard");
+    println!("(6) Nothing");
+    match input_number("Setting", 1..=6) {
+        1 => { settings.theme = input_theme(); },
+        2 => { settings.game = input_game_config(); },
+        3 => { settings.servers.clear(); },
+        4 => {
+            settings.keys = input_key_bindings(&settings.keys);
+            if let Err(e) = keys::check_shared(&settings.keys, &settings.shared_keys) {
+                println!("Note that {}, change the keys for sharing the keyboard before sharing it.", e);
+            }
+        },
+        5 => { settings.shared_keys = input_shared_keys(&settings.keys, &settings.shared_keys); },
+        _ => {},
+    }
+}
+
+/// ask for the keys of an action, until they are all known keys
+pub fn input_keys(action: &str) -> Vec<String> {
+    println!("Please type in the keys for {}, separated by spaces, such as: a Left", action);
+    loop {
+        let mut line = String::new();
+        stdin().read_line(&mut line).unwrap();
+        match line.split_whitespace().find(|k| keys::key_code(k).is_none()) {
+            Some(k) => { println!("Sorry, {}. Please try again:", KeyError::Unknown(k.to_string())); },
+            None => {
+                break line.split_whitespace()
+                    .filter_map(|k| keys::key_code(k).and_then(keys::key_name))
+                    .collect();
+            },
+        }
+    }
+}
+
+/// let the user rebind the keys or take a preset, the bindings are given back only if they can be played with
+pub fn input_key_bindings(current: &KeyBindings) -> KeyBindings {
+    let mut bindings = current.clone();
+    loop {
+        println!("Please choose an action to rebind, or a preset:");
+        let actions = bindings.actions();
+        let (presets, done) = (actions.len(), actions.len() + PRESETS.len());
+        for (i, (action, keys)) in actions.iter().enumerate() {
+            println!("({}) {}: {}", i + 1, action, keys.join(" "));
+        }
+        for (i, preset) in PRESETS.iter().enumerate() {
+            println!("({}) The {} preset", presets + i + 1, preset);
+        }
+        println!("({}) Done", done + 1);
+        println!("({}) Give up the changes", done + 2);
+        match input_number("Key bindings", 1..=(done + 2) as u64) as usize {
+            n if n <= presets => {
+                let action = actions[n - 1].0;
+                *bindings.keys_mut(action).unwrap() = input_keys(action);
+            },
+            n if n <= done => { bindings = KeyBindings::preset(PRESETS[n - presets - 1]).unwrap(); },
+            n if n == done + 1 => match bindings.check() {
+                Ok(()) => { break bindings; },
+                Err(e) => { println!("Sorry, {}. Please change it.", e); },
+            },
+            _ => { break current.clone(); },
+        }
+    }
+}
+
+/// the keys a seat navigates and boosts with, for telling
+fn navigation(keys: &KeyBindings) -> (String, String) {
+    ([&keys.up, &keys.left, &keys.down, &keys.right].map(|k| k.join("/")).join(" "), keys.boost.join(" "))
+}
+
+/// let the user rebind the keys of each seat sharing the keyboard, given back only if they can be played with
+///  - `keys`: the user's own, which chat, show the diagnostics and quit for everyone
+pub fn input_shared_keys(keys: &KeyBindings, current: &[KeyBindings]) -> Vec<KeyBindings> {
+    let mut seats = current.to_vec();
+    loop {
+        println!("Please choose a seat to rebind, yours is the first:");
+        for (i, seat) in seats.iter().enumerate() {
+            let (navigate, boost) = navigation(seat);
+            println!("({}) Navigating with {}, boosting with {}", i + 1, navigate, boost);
+        }
+        let (presets, done) = (seats.len(), seats.len() + 1);
+        println!("({}) The usual ones: {}", presets + 1, SHARED_LAYOUTS.join(", "));
+        println!("({}) Done", done + 1);
+        println!("({}) Give up the changes", done + 2);
+        match input_number("Seat", 1..=(done + 2) as u64) as usize {
+            n if n <= presets => {
+                for action in SHARED_ACTIONS {
+                    *seats[n - 1].keys_mut(action).unwrap() = input_keys(action);
+                }
+            },
+            n if n <= done => { seats = keys::shared_presets(); },
+            n if n == done + 1 => match keys::check_shared(keys, &seats) {
+                Ok(()) => { break seats; },
+                Err(e) => { println!("Sorry, {}. Please change it.", e); },
+            },
+            _ => { break current.to_vec(); },
+        }
+    }
+}
+
+/// let the user pick how smart the bots are
+pub fn input_bot_level() -> BotLevel {
+    println!("Please choose the difficulty of bots:");
+    for (i, level) in BOT_LEVELS.iter().enumerate() {
+        println!("({}) {}", i + 1, level.name());
+    }
+    BOT_LEVELS[input_number("Difficulty", 1..=BOT_LEVELS.len() as u64) as usize - 1]
+}
+
+/// let the user pick who plays the snake, a script file is loaded right away
+///  - `keys`, `shared`: the user's keys, and those of each seat when sharing the keyboard
+pub fn input_controller(keys: &KeyBindings, shared: &[KeyBindings]) -> ControllerChoice {
+    println!("Who plays your snake? The keyboard can always take over, and Esc quits.");
+    println!("(1) You, with the keyboard");
+    println!("(2) A bot");
+    println!("(3) A script, such as recorded inputs");
+    println!("(4) You and friends, sharing the keyboard");
+    match input_number("Player", 1..=4) {
+        2 => ControllerChoice::Bot(input_bot_level()),
+        3 => loop {
+            println!("Please input the path of the script:");
+            let mut path = String::new();
+            stdin().read_line(&mut path).unwrap();
+            match ScriptedController::load(path.trim()) {
+                Ok(s) => { break ControllerChoice::Script(path.trim().to_string(), s); },
+                Err(e) => { println!("Invalid script, {}.", e); },
+            }
+        },
+        4 => {
+            if let Err(e) = keys::check_shared(keys, shared) {
+                println!("Sorry, {}. Please change the keys for sharing the keyboard in the settings.", e);
+                return ControllerChoice::Keyboard;
+            }
+            let friends = input_number("How many friends play with you?", 1..=(shared.len() - 1) as u64);
+            let (navigate, boost) = navigation(&shared[0]);
+            println!("You navigate with {} and boost with {}, your keys to chat and quit are for everyone.", navigate, boost);
+            ControllerChoice::Shared((1..=friends as usize).map(|seat| {
+                let (navigate, boost) = navigation(&shared[seat]);
+                println!("Friend {} navigates with {} and boosts with {}.", seat, navigate, boost);
+                input_name()
+            }).collect())
+        },
+        _ => ControllerChoice::Keyboard,
+    }
+}
+
+/// ask for a name, until it is allowed
+pub fn input_name() -> String {
+    println!("Please enter your name ({} characters at most):", names::MAX_NAME_LEN);
+    loop {
+        let mut line = String::new();
+        stdin().read_line(&mut line).unwrap();
+        match names::validate_name(&line) {
+            Ok(n) => { break n; },
+            Err(e) => { println!("Sorry, {}. Please try another:", e); },
+        }
+    }
+}
+
+/// show the leaderboard and the latest games played on this computer, till Enter is pressed
+pub fn show_leaderboard() -> Result<()> {
+    stdout()
+        .execute(Clear(ClearType::All))?
+        .execute(cursor::MoveTo(0, 0))?
+        .execute(Print(TITLE))?;
+    match Records::load() {
+        Ok(r) if r.players.is_empty() => { println!("No games recorded yet, play alone or host one!"); },
+        Ok(r) => {
+            for line in r.leaderboard(LEADERBOARD_SHOWN) {
+                print!("{}", line.1);
+            }
+            println!("\nLatest games");
+            for m in r.history.iter().rev().take(HISTORY_SHOWN) {
+                let results: Vec<String> = m.results.iter()
+                    .map(|(name, game)| format!("{} ({})", name, game.describe()))
+                    .collect();
+                println!("{}: {}", m.mode, results.join(", "));
+            }
+        },
+        Err(e) => { println!("Can't show the leaderboard, {}", e); },
+    }
+    if let Some(path) = records::records_path() {
+        println!("\nGames played alone or hosted here are kept in {}", path.display());
+    }
+    println!("Press Enter to return to the menu");
+    let mut line = String::new();
+    stdin().read_line(&mut line).unwrap();
+    Ok(())
+}
+
 /// random from default names
 pub fn random_name() -> String {
     DEFAULT_NAMES.choose(&mut thread_rng()).unwrap().to_string()
 }
 
+/// a fresh asciicast file name for a match beginning now
+pub fn record_path() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs()).unwrap_or(0);
+    format!("socket_snake_{}.cast", secs)
+}
+
 /// show the menu, and returns the user's idea
-pub fn show_main_menu(name: &mut String) -> Result<UsersIdea> {
+/// the settings are changed as the user goes, but not saved
+pub fn show_main_menu(settings: &mut Settings, recording: bool, controller: &ControllerChoice) -> Result<UsersIdea> {
+    let controller_hint = match controller {
+        ControllerChoice::Keyboard => String::new(),
+        c => format!("{}{}", CONTROLLER_HINT, c.describe()),
+    };
     stdout()
         .execute(Clear(ClearType::All))?
         .execute(cursor::MoveTo(0, 0))?
         .execute(Print(TITLE))?
         .execute(Print(GREETING))?
-        .execute(Print(&format!("{}", &name)))?
+        .execute(Print(&settings.name))?
+        .execute(Print(if recording { RECORDING_HINT } else { "" }))?
+        .execute(Print(&controller_hint))?
         .execute(Print(MENU_HINT))?
         .execute(cursor::Show).unwrap();
     let mut line = String::new();
@@ -121,14 +501,27 @@ pub fn show_main_menu(name: &mut String) -> Result<UsersIdea> {
     };
     match choice {
         1 => {
-            Ok(UsersIdea::Singleplayer)
+            Ok(UsersIdea::Singleplayer(input_usual_game_config(&mut settings.game)))
         },
         2 => {
-            let addr = input_ip_addr_port();
-            Ok(UsersIdea::JoinGame(addr))
+            let addr = input_server(&settings.servers);
+            settings.remember_server(&addr);
+            let team = match input_number(
+                    "Which team do you want to play for, if the host plays teams? 0 for any", 0..=MAX_TEAMS as u64) {
+                0 => None,
+                t => Some(t as u8 - 1),
+            };
+            Ok(UsersIdea::JoinGame(addr, team))
         },
         3 => {
+            let config = input_usual_game_config(&mut settings.game);
+            let delivery = if input_yes_no("Send screens to each player instead of multicasting, if multicast is blocked?") {
+                Delivery::Unicast
+            } else {
+                Delivery::Multicast
+            };
             let mut server_local_ip = local_ipaddress::get().unwrap();
+            let bot_addr = format!("{}{}", server_local_ip, multiplayer::BOT_SERVER_PORT);
             server_local_ip.push_str(multiplayer::TCP_SERVER_PORT);
             stdout()
                 .execute(Clear(ClearType::All))?
@@ -136,18 +529,31 @@ pub fn show_main_menu(name: &mut String) -> Result<UsersIdea> {
                 .execute(Print(TITLE))?
                 .execute(Print(HOST_HINT))?
                 .execute(Print(&server_local_ip))?
+                .execute(Print(BOT_HINT))?
+                .execute(Print(&bot_addr))?
                 .execute(Print(SEPERATOR))?
                 .execute(cursor::Show).unwrap();
-            Ok(UsersIdea::HostGame(server_local_ip))
+            Ok(UsersIdea::HostGame(server_local_ip, config, delivery))
         },
         4 => {
-            println!("Please enter your name:");
-            let mut line = String::new();
-            stdin().read_line(&mut line).unwrap();
-            *name = line.trim().to_string();
-            Ok(UsersIdea::ChangeName)
+            Ok(UsersIdea::ToggleRecording)
         },
         5 => {
+            settings.name = input_name();
+            Ok(UsersIdea::ChangeName)
+        },
+        6 => {
+            Ok(UsersIdea::ChangeController(input_controller(&settings.keys, &settings.shared_keys)))
+        },
+        7 => {
+            input_settings(settings);
+            Ok(UsersIdea::ChangeSettings)
+        },
+        8 => {
+            show_leaderboard()?;
+            Ok(UsersIdea::ShowLeaderboard)
+        },
+        9 => {
             Ok(UsersIdea::ExitGame)
         },
         _ => Err(Error::new(ErrorKind::Other, "Choice out of range")),
@@ -0,0 +1,348 @@
+//! pub mod tournament: bots playing against each other without any screen
+//! every game is seeded, so a tournament is replayed exactly with the same seed,
+//! which makes it handy to compare bot changes and to catch rule regressions
+//!
+//! run it as `socket_snake tournament [options]`, see `USAGE`
+
+use crate::bot;
+use crate::map::YardMap;
+use crate::yard::{ GameConfig, YardSim, BotLevel, BOT_LEVELS, POWER_UPS, MAX_PLAYERS };
+
+use std::fs;
+use std::io::{ self, Write };
+use std::thread;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::Serialize;
+
+pub const USAGE: &str = r#"Usage: socket_snake tournament [options]
+    --games N          games to play, 1000 by default
+    --seed S           seed of the first game, the next ones count up, 0 by default
+    --bots A,B,...     levels of the bots, easy, normal or hard, normal,hard by default
+    --max-ticks T      a game is over after T ticks, 2000 by default
+    --map NAME|PATH    a built-in map or a map file, the open field by default
+    --wrap             play in a wrap-around arena
+    --power-ups        enable all the power-ups
+    --format csv|json  how the results are written, csv by default
+    --out PATH         write the results to the file instead of the standard output
+"#;
+
+/// how the results are written
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+}
+
+/// everything about a tournament
+#[derive(Clone, Debug)]
+pub struct TournamentConfig {
+    pub games: u64,
+    pub seed: u64,
+    pub bots: Vec<BotLevel>,
+    pub max_ticks: u64,
+    pub game: GameConfig,
+    pub format: OutputFormat,
+    pub out: Option<String>,
+}
+
+impl Default for TournamentConfig {
+    fn default() -> TournamentConfig {
+        TournamentConfig {
+            games: 1000,
+            seed: 0,
+            bots: vec![BotLevel::Normal, BotLevel::Hard],
+            max_ticks: 2000,
+            game: GameConfig::default(),
+            format: OutputFormat::Csv,
+            out: None,
+        }
+    }
+}
+
+/// how one seat did through the tournament
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SeatStats {
+    pub seat: usize,
+    pub level: String,
+    pub games: u64,
+    pub wins: u64,
+    pub draws: u64,
+    pub win_rate: f64,
+    pub avg_length: f64,
+    pub avg_survival_ticks: f64,
+    #[serde(skip)]
+    total_length: u64,
+    #[serde(skip)]
+    total_survival: u64,
+}
+
+impl SeatStats {
+    fn merge(&mut self, other: &SeatStats) {
+        self.games += other.games;
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.total_length += other.total_length;
+        self.total_survival += other.total_survival;
+    }
+
+    fn finish(&mut self) {
+        if self.games > 0 {
+            let games = self.games as f64;
+            self.win_rate = self.wins as f64 / games;
+            self.avg_length = self.total_length as f64 / games;
+            self.avg_survival_ticks = self.total_survival as f64 / games;
+        }
+    }
+}
+
+/// how one seat did in one game
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SeatResult {
+    pub won: bool,
+    pub draw: bool,
+    pub length: usize,
+    pub survival_ticks: u64,
+}
+
+/// play one game till one bot is left, everyone is dead, or time is up
+/// a single bot wins by surviving till the time is up
+/// when time is up, the longest survivor wins, and ties are draws
+/// so are the last snakes dying in the same tick
+pub fn play_game(config: &TournamentConfig, seed: u64) -> Vec<SeatResult> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut y = YardSim::with_seed(&config.game, rng.gen());
+    let mut seats: Vec<Option<u8>> = Vec::new();    // ids on the field, `None` when dead
+    let mut bots = Vec::new();
+    for level in &config.bots {
+        seats.push(y.init_snake());
+        bots.push(bot::new_seeded_bot(*level, rng.gen()));
+    }
+    let mut results = vec![SeatResult { won: false, draw: false, length: 0, survival_ticks: 0 }; seats.len()];
+    let mut tick = 0;
+    while tick < config.max_ticks {
+        let alive = seats.iter().filter(|s| s.is_some()).count();
+        if alive == 0 || alive == 1 && seats.len() > 1 {
+            break;
+        }
+        let state = y.snapshot();
+        for (seat, id) in seats.iter().enumerate() {
+            if let Some(id) = id {
+                if let Some(s) = state.snake(*id) {
+                    results[seat].length = s.body.len();
+                }
+                if let Some(d) = bots[seat].decide(&state, *id) {
+                    y.control_snake(*id, d);
+                }
+            }
+        }
+        let (_score, failed) = y.next_tick();
+        tick += 1;
+        for (seat, id) in seats.iter_mut().enumerate() {
+            match id {
+                Some(fid) if failed[*fid as usize] => {
+                    results[seat].survival_ticks = tick;
+                    *id = None;
+                },
+                _ => {},
+            }
+        }
+    }
+    // the survivors
+    let state = y.snapshot();
+    for (seat, id) in seats.iter().enumerate() {
+        if let Some(s) = id.and_then(|id| state.snake(id)) {
+            results[seat].length = s.body.len();
+            results[seat].survival_ticks = tick;
+        }
+    }
+    let longest = seats.iter().enumerate()
+        .filter(|(_seat, id)| id.is_some())
+        .map(|(seat, _id)| results[seat].length)
+        .max();
+    let leaders: Vec<usize> = match longest {
+        Some(longest) => (0..seats.len())
+            .filter(|seat| seats[*seat].is_some() && results[*seat].length == longest)
+            .collect(),
+        // the last ones died together, such as bumping heads
+        None => (0..seats.len())
+            .filter(|seat| results[*seat].survival_ticks == tick)
+            .collect(),
+    };
+    for seat in &leaders {
+        results[*seat].won = leaders.len() == 1 && longest.is_some();
+        results[*seat].draw = leaders.len() > 1;
+    }
+    results
+}
+
+/// play all the games on every core, and sum up by seats
+pub fn run(config: &TournamentConfig) -> Vec<SeatStats> {
+    let workers = thread::available_parallelism().map(|n| n.get() as u64).unwrap_or(1);
+    let handles: Vec<_> = (0..workers)
+        .map(|worker| {
+            let config = config.clone();
+            thread::spawn(move || {
+                let mut stats = vec![SeatStats::default(); config.bots.len()];
+                let mut game = worker;
+                while game < config.games {
+                    let results = play_game(&config, config.seed.wrapping_add(game));
+                    for (seat, r) in results.iter().enumerate() {
+                        let s = &mut stats[seat];
+                        s.games += 1;
+                        s.wins += r.won as u64;
+                        s.draws += r.draw as u64;
+                        s.total_length += r.length as u64;
+                        s.total_survival += r.survival_ticks;
+                    }
+                    game += workers;
+                }
+                stats
+            })
+        })
+        .collect();
+    let mut stats: Vec<SeatStats> = config.bots.iter().enumerate()
+        .map(|(seat, level)| SeatStats { seat, level: level.name().to_string(), ..SeatStats::default() })
+        .collect();
+    for h in handles {
+        for (seat, s) in h.join().unwrap().iter().enumerate() {
+            stats[seat].merge(s);
+        }
+    }
+    for s in &mut stats {
+        s.finish();
+    }
+    stats
+}
+
+/// write the stats as a csv table, one row for each seat
+pub fn write_csv<W: Write>(w: &mut W, stats: &[SeatStats]) -> io::Result<()> {
+    writeln!(w, "seat,level,games,wins,draws,win_rate,avg_length,avg_survival_ticks")?;
+    for s in stats {
+        writeln!(
+            w, "{},{},{},{},{},{:.4},{:.2},{:.2}",
+            s.seat, s.level, s.games, s.wins, s.draws, s.win_rate, s.avg_length, s.avg_survival_ticks,
+        )?;
+    }
+    Ok(())
+}
+
+/// write the stats as a json array, one object for each seat
+pub fn write_json<W: Write>(w: &mut W, stats: &[SeatStats]) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *w, stats)?;
+    writeln!(w)
+}
+
+fn parse_level(s: &str) -> Result<BotLevel, String> {
+    BOT_LEVELS.iter()
+        .find(|l| l.name().eq_ignore_ascii_case(s.trim()))
+        .copied()
+        .ok_or(format!("unknown bot level \"{}\"", s))
+}
+
+fn parse_number(option: &str, value: &str) -> Result<u64, String> {
+    value.parse().map_err(|_e| format!("{} expects a number, found \"{}\"", option, value))
+}
+
+/// a built-in map by its name, or else a map file
+fn parse_map(s: &str) -> Result<YardMap, String> {
+    match YardMap::builtins().into_iter().find(|m| m.name.eq_ignore_ascii_case(s)) {
+        Some(m) => Ok(m),
+        None => YardMap::load(s).map_err(|e| e.to_string()),
+    }
+}
+
+/// read the options following `tournament` on the command line
+pub fn parse_args(args: &[String]) -> Result<TournamentConfig, String> {
+    let mut config = TournamentConfig::default();
+    let mut iter = args.iter();
+    while let Some(option) = iter.next() {
+        let mut value = || iter.next().ok_or(format!("{} expects a value", option));
+        match option.as_str() {
+            "--games" => { config.games = parse_number(option, value()?)?; },
+            "--seed" => { config.seed = parse_number(option, value()?)?; },
+            "--max-ticks" => { config.max_ticks = parse_number(option, value()?)?; },
+            "--bots" => {
+                config.bots = value()?.split(',').map(parse_level).collect::<Result<_, _>>()?;
+            },
+            "--map" => { config.game.map = Some(parse_map(value()?)?); },
+            "--wrap" => { config.game.wrap_around = true; },
+            "--power-ups" => { config.game.power_ups = POWER_UPS.to_vec(); },
+            "--format" => {
+                config.format = match value()?.as_str() {
+                    "csv" => OutputFormat::Csv,
+                    "json" => OutputFormat::Json,
+                    f => { return Err(format!("unknown format \"{}\"", f)); },
+                };
+            },
+            "--out" => { config.out = Some(value()?.clone()); },
+            _ => { return Err(format!("unknown option \"{}\"", option)); },
+        }
+    }
+    if config.bots.is_empty() || config.bots.len() > MAX_PLAYERS as usize {
+        return Err(format!("there shall be 1 to {} bots", MAX_PLAYERS));
+    }
+    Ok(config)
+}
+
+/// the `tournament` command: parse, play and write the results
+pub fn run_cli(args: &[String]) -> Result<(), String> {
+    let config = parse_args(args)?;
+    let stats = run(&config);
+    let mut out: Box<dyn Write> = match &config.out {
+        Some(path) => Box::new(fs::File::create(path).map_err(|e| e.to_string())?),
+        None => Box::new(io::stdout()),
+    };
+    match config.format {
+        OutputFormat::Csv => write_csv(&mut out, &stats),
+        OutputFormat::Json => write_json(&mut out, &stats),
+    }.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn same_seed_plays_the_same_game() {
+        let config = TournamentConfig {
+            bots: vec![BotLevel::Easy, BotLevel::Normal, BotLevel::Hard],
+            max_ticks: 300,
+            ..TournamentConfig::default()
+        };
+        for seed in 0..3 {
+            assert_eq!(play_game(&config, seed), play_game(&config, seed));
+        }
+    }
+
+    #[test]
+    fn a_game_has_one_winner_or_draws() {
+        let config = TournamentConfig { max_ticks: 300, ..TournamentConfig::default() };
+        let results = play_game(&config, 7);
+        assert_eq!(results.len(), config.bots.len());
+        let wins = results.iter().filter(|r| r.won).count();
+        assert!(wins <= 1);
+        assert!(wins == 0 || results.iter().all(|r| !r.draw));
+    }
+
+    #[test]
+    fn parse_args_takes_the_options() {
+        let config = parse_args(&args("--games 10 --seed 3 --bots easy,HARD --map cross --wrap --format json")).unwrap();
+        assert_eq!(config.games, 10);
+        assert_eq!(config.seed, 3);
+        assert_eq!(config.bots, vec![BotLevel::Easy, BotLevel::Hard]);
+        assert_eq!(config.game.map.unwrap().name, "Cross");
+        assert!(config.game.wrap_around);
+        assert_eq!(config.format, OutputFormat::Json);
+
+        assert!(parse_args(&args("--games many")).is_err());
+        assert!(parse_args(&args("--seed")).is_err());
+        assert!(parse_args(&args("--bots genius")).is_err());
+        assert!(parse_args(&args("--fast")).is_err());
+    }
+}
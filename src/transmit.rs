@@ -2,9 +2,14 @@
 /// wrapped TCP and UDP scaffolds, send serialized and recv parsed
 
 use serde::{ Serialize };
+use serde::de::DeserializeOwned;
 
-pub use std::io::{ Read, Write, Result, Error, ErrorKind };
-use std::net::{ TcpStream, UdpSocket };
+pub use std::io::{ Read, Write, BufRead, Result, Error, ErrorKind };
+use std::net::{ TcpStream, UdpSocket, ToSocketAddrs };
+
+// the receiving macros expand in other crates too
+#[doc(hidden)]
+pub use bincode;
 
 pub const TCP_BUFFER_SIZE: usize = 1024;
 pub const UDP_BUFFER_SIZE: usize = 65507;
@@ -22,15 +27,19 @@ pub fn tcp_send<T: Serialize>(stream: &mut TcpStream, obj: &T) -> Result<()> {
 macro_rules! tcp_recv{
     ($stream: expr) => {
         {
-            let mut buffer = [0; TCP_BUFFER_SIZE];
+            use std::io::Read as _;
+            let mut buffer = [0; $crate::transmit::TCP_BUFFER_SIZE];
             let mut len = 0;
             loop {
                 let inc_len = match $stream.read(&mut buffer[len..]) {
+                    Ok(0) => {
+                        break Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "connection closed"));
+                    },
                     Ok(l) => l,
                     Err(e) => { break Err(e); },
                 };
                 len += inc_len;
-                match bincode::deserialize(&buffer[..len]) {
+                match $crate::transmit::bincode::deserialize(&buffer[..len]) {
                     Ok(obj) => { break Ok(obj); },
                     _ => { continue; },
                 };
@@ -39,9 +48,9 @@ macro_rules! tcp_recv{
     }
 }
 
-pub fn udp_send<T: Serialize>(socket: &UdpSocket, src: &str, obj: &T) -> Result<()> {
+pub fn udp_send<T: Serialize, A: ToSocketAddrs>(socket: &UdpSocket, dst: A, obj: &T) -> Result<()> {
     let serialized: Vec<u8> = bincode::serialize(&obj).unwrap();
-    socket.send_to(&serialized, src)?;
+    socket.send_to(&serialized, dst)?;
     Ok(())
 }
 
@@ -49,7 +58,7 @@ pub fn udp_send<T: Serialize>(socket: &UdpSocket, src: &str, obj: &T) -> Result<
 macro_rules! udp_recv{
     ($socket: expr) => {
         {
-            let mut buffer = [0; UDP_BUFFER_SIZE];
+            let mut buffer = [0; $crate::transmit::UDP_BUFFER_SIZE];
             let mut len = 0;
             loop {
                 let (amt, _src) = match $socket.recv_from(&mut buffer[len..]) {
@@ -57,11 +66,34 @@ macro_rules! udp_recv{
                     Err(e) => { break Err(e); },
                 };
                 len += amt;
-                match bincode::deserialize(&buffer[..len]) {
+                match $crate::transmit::bincode::deserialize(&buffer[..len]) {
                     Ok(obj) => { break Ok(obj); },
                     Err(_) => { continue; },
                 };
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// the line-based JSON protocol for programmatic players, one object each line
+pub fn json_send<T: Serialize, W: Write>(stream: &mut W, obj: &T) -> Result<()> {
+    let mut serialized: Vec<u8> = serde_json::to_vec(&obj)?;
+    serialized.push(b'\n');
+    stream.write_all(&serialized)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// read a line and parse it, blank lines are skipped
+pub fn json_recv<T: DeserializeOwned, R: BufRead>(reader: &mut R) -> Result<T> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "connection closed"));
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        return serde_json::from_str(&line).map_err(|e| Error::new(ErrorKind::InvalidData, e));
+    }
+}
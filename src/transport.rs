@@ -0,0 +1,580 @@
+//! pub mod transport: how the client and the server are connected
+//! the client sends `YardCtrl`s and receives `YardInfo`s, the server does the opposite,
+//! whether through channels in the same process or through sockets
+//! infos by UDP are numbered, so the client can tell which were lost or came late
+//!  - `channel_pair`: both ends in the same process, for singleplayer
+//!  - `NetServer` and `NetClient`: controls go by TCP, infos by UDP multicast or unicast
+
+use crate::server::{ YardCtrl, YardInfo };
+use crate::transmit::*;
+use crate::{ tcp_recv, udp_recv };
+
+use std::thread;
+use std::collections::{ BTreeSet, HashMap };
+use std::sync::{ Arc, Mutex };
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::sync::mpsc::{ self, Sender, Receiver, TryRecvError, RecvTimeoutError };
+use std::net::{ Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket, Ipv4Addr };
+use std::time::Duration;
+
+pub const UDP_SERVER_PORT: &str = "0.0.0.0:19198";
+pub const UDP_CLIENT_PORT: &str = "0.0.0.0:10114";
+// multicast group can be from 234.0.2.0 to 238.255.255.255
+pub const MULTICAST_GROUP_PORT: &str = "234.51.4.19:10114";
+pub const MULTICAST_GROUP_ADDR: &Ipv4Addr = &Ipv4Addr::new(234, 51, 4, 19);
+
+/// the client's end of a connection
+pub trait ClientTransport: Send + Sync {
+    /// send a control to the server
+    fn send_ctrl(&self, ctrl: YardCtrl) -> Result<()>;
+    /// wait for an info from the server, `None` if nothing comes in time
+    fn recv_info(&self, timeout: Duration) -> Result<Option<YardInfo>>;
+    /// how infos have come through so far, nothing is lost on reliable links
+    fn link_stats(&self) -> LinkStats {
+        LinkStats::default()
+    }
+    /// end the connection, the players of this client leave the game
+    fn close(&self) {}
+}
+
+/// how numbered infos have come through a lossy link
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LinkStats {
+    pub received: u64,
+    /// never came, as far as known yet
+    pub dropped: u64,
+    /// came after a later one
+    pub out_of_order: u64,
+    /// came more than once
+    pub duplicated: u64,
+}
+
+/// the numbers missed are remembered this far back, older ones are surely dropped
+const MISSING_WINDOW: u64 = 1024;
+
+/// counts the numbered infos as they come
+#[derive(Default)]
+pub(crate) struct SeqTracker {
+    /// the number expected next, `None` before the first one
+    next: Option<u64>,
+    /// numbers skipped, which may still come late
+    missing: BTreeSet<u64>,
+    pub(crate) stats: LinkStats,
+}
+
+impl SeqTracker {
+    pub(crate) fn count(&mut self, seq: u64) {
+        self.stats.received += 1;
+        match self.next {
+            Some(next) if seq < next => {
+                if self.missing.remove(&seq) {
+                    // it was counted as dropped when a later one came
+                    self.stats.out_of_order += 1;
+                    self.stats.dropped -= 1;
+                } else {
+                    self.stats.duplicated += 1;
+                }
+            },
+            Some(next) => {
+                self.stats.dropped += seq - next;
+                self.missing.extend(next.max(seq.saturating_sub(MISSING_WINDOW))..seq);
+                self.next = Some(seq + 1);
+            },
+            None => { self.next = Some(seq + 1); },
+        }
+        // `missing` keeps the recent ones only
+        while self.missing.first().is_some_and(|m| m + MISSING_WINDOW < seq) {
+            self.missing.pop_first();
+        }
+    }
+}
+
+/// the server's end of the connections, to every client at once
+pub trait ServerTransport: Send + Sync {
+    /// send an info to all the clients
+    fn send_info(&self, info: YardInfo) -> Result<()>;
+    /// take a control from any client, `None` if there is none now
+    fn try_recv_ctrl(&self) -> Result<Option<YardCtrl>>;
+    /// stop serving
+    fn close(&self) {}
+}
+
+fn disconnected() -> Error {
+    Error::new(ErrorKind::NotConnected, "the other end is gone")
+}
+
+/// the client's end of an in-process connection
+pub struct ChannelClient {
+    ctrl_tx: Sender<YardCtrl>,
+    info_rx: Mutex<Receiver<YardInfo>>,
+}
+
+/// the server's end of an in-process connection
+pub struct ChannelServer {
+    info_tx: Sender<YardInfo>,
+    ctrl_rx: Mutex<Receiver<YardCtrl>>,
+}
+
+/// connect a client and a server in the same process
+pub fn channel_pair() -> (ChannelClient, ChannelServer) {
+    // server sends to clients
+    let (info_tx, info_rx) = mpsc::channel();
+    // client sends to servers
+    let (ctrl_tx, ctrl_rx) = mpsc::channel();
+    (
+        ChannelClient { ctrl_tx, info_rx: Mutex::new(info_rx) },
+        ChannelServer { info_tx, ctrl_rx: Mutex::new(ctrl_rx) },
+    )
+}
+
+impl ClientTransport for ChannelClient {
+    fn send_ctrl(&self, ctrl: YardCtrl) -> Result<()> {
+        self.ctrl_tx.send(ctrl).map_err(|_e| disconnected())
+    }
+
+    fn recv_info(&self, timeout: Duration) -> Result<Option<YardInfo>> {
+        match self.info_rx.lock().unwrap().recv_timeout(timeout) {
+            Ok(info) => Ok(Some(info)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(disconnected()),
+        }
+    }
+}
+
+impl ServerTransport for ChannelServer {
+    fn send_info(&self, info: YardInfo) -> Result<()> {
+        self.info_tx.send(info).map_err(|_e| disconnected())
+    }
+
+    fn try_recv_ctrl(&self) -> Result<Option<YardCtrl>> {
+        match self.ctrl_rx.lock().unwrap().try_recv() {
+            Ok(ctrl) => Ok(Some(ctrl)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(disconnected()),
+        }
+    }
+}
+
+/// how the server delivers infos by UDP
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Delivery {
+    /// once to the multicast group, for every client in the local network
+    Multicast,
+    /// once to each client, for networks without multicast
+    Unicast,
+}
+
+/// which connection each player joined from, so a connection only speaks for its own players
+/// the ids of players are sent to everyone, so they can't be trusted alone
+#[derive(Clone, Default)]
+pub struct Claims {
+    /// the connection of each player
+    owners: Arc<Mutex<HashMap<u64, u64>>>,
+    /// the number of the next connection
+    next: Arc<AtomicU64>,
+}
+
+impl Claims {
+    /// a number for a new connection
+    pub fn connect(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// whether the control from the connection may be passed on to the server
+    ///  - `NewSnake` claims the player for the connection, unless someone has claimed it
+    ///  - the other controls about a player only come from the connection that claimed it
+    pub fn admit(&self, conn: u64, ctrl: &YardCtrl) -> bool {
+        let rid = match ctrl.rid() {
+            Some(rid) => rid,
+            None => { return true; },
+        };
+        let mut owners = self.owners.lock().unwrap();
+        match (ctrl, owners.get(&rid)) {
+            (YardCtrl::NewSnake(..), None) => {
+                owners.insert(rid, conn);
+                true
+            },
+            (YardCtrl::NewSnake(..), Some(_)) => false,
+            (YardCtrl::LeaveGame(_), Some(c)) if *c == conn => {
+                owners.remove(&rid);
+                true
+            },
+            (_, Some(c)) => *c == conn,
+            (_, None) => false,
+        }
+    }
+
+    /// the player didn't join, the id may be claimed again
+    pub fn release(&self, rid: u64) {
+        self.owners.lock().unwrap().remove(&rid);
+    }
+
+    /// the connection is gone, return the players it had
+    pub fn disconnect(&self, conn: u64) -> Vec<u64> {
+        let mut owners = self.owners.lock().unwrap();
+        let rids: Vec<u64> = owners.iter().filter(|(_, c)| **c == conn).map(|(rid, _)| *rid).collect();
+        for rid in &rids {
+            owners.remove(rid);
+        }
+        rids
+    }
+}
+
+/// the server's end of network connections
+/// controls come by TCP, and infos go by UDP
+pub struct NetServer {
+    delivery: Delivery,
+    socket: UdpSocket,
+    /// the number of the next info
+    seq: AtomicU64,
+    ctrl_tx: Sender<YardCtrl>,
+    ctrl_rx: Mutex<Receiver<YardCtrl>>,
+    /// where infos go when unicasting
+    peers: Arc<Mutex<Vec<SocketAddr>>>,
+    /// others interested in infos, such as bots
+    subscribers: Mutex<Vec<Sender<YardInfo>>>,
+    /// the players of each connection
+    claims: Claims,
+}
+
+/// receive controls from a client till it quits, pass them through `ctrl_tx`
+/// shake hands first, telling the multicast group, or taking the UDP port to unicast to, the connection is dropped if that fails
+/// pings are answered right away by TCP, without bothering the game
+/// controls of players that joined from other connections are dropped, see `Claims`
+pub fn handle_connection(
+        ctrl_tx: Sender<YardCtrl>,
+        mut stream: TcpStream,
+        delivery: Delivery,
+        peers: Arc<Mutex<Vec<SocketAddr>>>,
+        claims: Claims,
+    ) {
+    println!("Connected one client, establishing UDP connection");
+    let peer = match delivery {
+        Delivery::Multicast => {
+            // send the multicast group for the client to join
+            if let Err(e) = tcp_send(&mut stream, MULTICAST_GROUP_ADDR) {
+                println!("Handshake failed {}", e);
+                return;
+            }
+            println!("UDP Multicast address sent");
+            None
+        },
+        Delivery::Unicast => {
+            // no group means unicast, the client tells where to send
+            if let Err(e) = tcp_send(&mut stream, &Ipv4Addr::UNSPECIFIED) {
+                println!("Handshake failed {}", e);
+                return;
+            }
+            let port: u16 = match tcp_recv!(stream) {
+                Ok(p) => p,
+                Err(e) => {
+                    println!("Handshake failed {}", e);
+                    return;
+                },
+            };
+            let peer = match stream.peer_addr() {
+                Ok(addr) => SocketAddr::new(addr.ip(), port),
+                Err(e) => {
+                    println!("Handshake failed {}", e);
+                    return;
+                },
+            };
+            peers.lock().unwrap().push(peer);
+            if let Err(e) = tcp_send(&mut stream, &true) {
+                println!("Handshake failed {}", e);
+                peers.lock().unwrap().retain(|p| *p != peer);
+                return;
+            }
+            println!("UDP unicast to {}", peer);
+            Some(peer)
+        },
+    };
+    thread::spawn(move || {
+        let conn = claims.connect();
+        loop {
+            let op: YardCtrl = match tcp_recv!(stream) {
+                Ok(YardCtrl::QuitGame) => {
+                    println!("Quit game intercepted, releasing connection");
+                    break;
+                },
+                Ok(c) => { c },
+                Err(e) => {
+                    println!("Receiving failed {}", e);
+                    break;
+                },
+            };
+            match op {
+                YardCtrl::Ping(n) => {
+                    tcp_send(&mut stream, &YardInfo::Pong(n)).unwrap_or(());
+                    continue;
+                },
+                _ if !claims.admit(conn, &op) => {
+                    println!("Dropped a ctrl of another connection's player {:?}", op);
+                    continue;
+                },
+                _ => {},
+            }
+            match ctrl_tx.send(op.clone()) {
+                Ok(_) => {
+                    println!("Request handled: {:?}", op);
+                },
+                Err(_) => {
+                    println!("Server quitted, ending TCP connection");
+                    break;
+                }
+            };
+        }
+        // the players are gone with the connection
+        for rid in claims.disconnect(conn) {
+            ctrl_tx.send(YardCtrl::LeaveGame(rid)).unwrap_or(());
+        }
+        if let Some(peer) = peer {
+            peers.lock().unwrap().retain(|p| *p != peer);
+        }
+        stream.shutdown(Shutdown::Both).unwrap_or(());
+    });
+}
+
+impl NetServer {
+    /// listen for clients on `server_addr`, and deliver infos the given way
+    pub fn bind(server_addr: &str, delivery: Delivery) -> Result<NetServer> {
+        let socket = UdpSocket::bind(UDP_SERVER_PORT)?;
+        if delivery == Delivery::Multicast {
+            socket.join_multicast_v4(MULTICAST_GROUP_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+            socket.set_multicast_loop_v4(false)?;
+        }
+        let (ctrl_tx, ctrl_rx) = mpsc::channel();
+        let peers = Arc::new(Mutex::new(Vec::new()));
+        // communicate through TCP
+        let listener = TcpListener::bind(server_addr)?;
+        println!("Listening");
+        let listener_ctrl_tx = Sender::clone(&ctrl_tx);
+        let listener_peers = Arc::clone(&peers);
+        let claims = Claims::default();
+        let listener_claims = claims.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        stream.set_read_timeout(Some(Duration::from_secs(100)))
+                            .expect("set_read_timeout call failed");
+                        // spawn a child that sends ctrl to the backend
+                        handle_connection(
+                            Sender::clone(&listener_ctrl_tx), stream, delivery, Arc::clone(&listener_peers),
+                            listener_claims.clone(),
+                        );
+                    },
+                    Err(_e) => {},
+                }
+            }
+        });
+        Ok(NetServer {
+            delivery,
+            socket,
+            seq: AtomicU64::new(0),
+            ctrl_tx,
+            ctrl_rx: Mutex::new(ctrl_rx),
+            peers,
+            subscribers: Mutex::new(Vec::new()),
+            claims,
+        })
+    }
+
+    /// a sender for controls coming in some other way
+    pub fn ctrl_sender(&self) -> Sender<YardCtrl> {
+        Sender::clone(&self.ctrl_tx)
+    }
+
+    /// the players of each connection, for controls coming in some other way
+    pub fn claims(&self) -> Claims {
+        self.claims.clone()
+    }
+
+    /// receive a copy of every info sent from now on
+    pub fn subscribe(&self) -> Receiver<YardInfo> {
+        let (info_tx, info_rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(info_tx);
+        info_rx
+    }
+}
+
+impl ServerTransport for NetServer {
+    fn send_info(&self, info: YardInfo) -> Result<()> {
+        let numbered = (self.seq.fetch_add(1, Ordering::Relaxed), &info);
+        match self.delivery {
+            Delivery::Multicast => { udp_send(&self.socket, MULTICAST_GROUP_PORT, &numbered)?; },
+            Delivery::Unicast => {
+                for peer in self.peers.lock().unwrap().iter() {
+                    udp_send(&self.socket, peer, &numbered).unwrap_or(()); // the connection handles leaving
+                }
+            },
+        }
+        self.subscribers.lock().unwrap().retain(|tx| tx.send(info.clone()).is_ok());
+        if let YardInfo::Refused(rid, _) = info {
+            self.claims.release(rid);
+        }
+        // printing information
+        match info {
+            YardInfo::RegisteredSnake(_, _) | YardInfo::Failed(_) => {
+                println!("Sent signal {:?}", info);
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn try_recv_ctrl(&self) -> Result<Option<YardCtrl>> {
+        match self.ctrl_rx.lock().unwrap().try_recv() {
+            Ok(ctrl) => Ok(Some(ctrl)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(disconnected()),
+        }
+    }
+}
+
+/// the client's end of a network connection
+/// controls go by TCP, and infos come by UDP as the server delivers, besides pongs by TCP
+pub struct NetClient {
+    stream: Mutex<TcpStream>,
+    socket: UdpSocket,
+    /// infos that came by TCP
+    tcp_rx: Mutex<Receiver<YardInfo>>,
+    tracker: Mutex<SeqTracker>,
+}
+
+impl NetClient {
+    /// connect to the server, retrying till it is there, then set up UDP as it tells
+    pub fn connect(server_addr: &str) -> Result<NetClient> {
+        // sending ctrl signal using TCP
+        println!("Connecting to {} ...", server_addr);
+        let mut stream = loop {
+            match TcpStream::connect(server_addr) {
+                Ok(s) => { break s; },
+                Err(e) => { println!("{}, retrying ...", e); },
+            };
+        };
+        // when TCP connected, use TCP to receive a multicast group
+        let group_addr: Ipv4Addr = tcp_recv!(stream)?;
+        let socket = if group_addr.is_unspecified() {
+            // unicast, tell the server where to send
+            let socket = UdpSocket::bind("0.0.0.0:0")?;
+            tcp_send(&mut stream, &socket.local_addr()?.port())?;
+            let _ack: bool = tcp_recv!(stream)?;
+            println!("Unicast set up, firing up game!");
+            socket
+        } else {
+            // start listening UDP for buffer and information
+            let socket = UdpSocket::bind(UDP_CLIENT_PORT)?;
+            socket.join_multicast_v4(&group_addr, &Ipv4Addr::UNSPECIFIED)?;
+            println!("Multicast set up, firing up game!");
+            socket
+        };
+        // the server answers by TCP too
+        let (tcp_tx, tcp_rx) = mpsc::channel();
+        let mut read_stream = stream.try_clone()?;
+        thread::spawn(move || {
+            while let Ok(info) = tcp_recv!(read_stream) {
+                if tcp_tx.send(info).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(NetClient {
+            stream: Mutex::new(stream),
+            socket,
+            tcp_rx: Mutex::new(tcp_rx),
+            tracker: Mutex::new(SeqTracker::default()),
+        })
+    }
+}
+
+impl ClientTransport for NetClient {
+    fn send_ctrl(&self, ctrl: YardCtrl) -> Result<()> {
+        tcp_send(&mut self.stream.lock().unwrap(), &ctrl)
+    }
+
+    fn recv_info(&self, timeout: Duration) -> Result<Option<YardInfo>> {
+        if let Ok(info) = self.tcp_rx.lock().unwrap().try_recv() {
+            return Ok(Some(info));
+        }
+        self.socket.set_read_timeout(Some(timeout))?;
+        match udp_recv!(&self.socket) {
+            Ok((seq, info)) => {
+                self.tracker.lock().unwrap().count(seq);
+                Ok(Some(info))
+            },
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn link_stats(&self) -> LinkStats {
+        self.tracker.lock().unwrap().stats
+    }
+
+    fn close(&self) {
+        // shutdown TCP connection
+        self.stream.lock().unwrap().shutdown(Shutdown::Both).unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yard::Direction;
+
+    fn counted(seqs: &[u64]) -> LinkStats {
+        let mut tracker = SeqTracker::default();
+        for seq in seqs {
+            tracker.count(*seq);
+        }
+        tracker.stats
+    }
+
+    #[test]
+    fn seq_tracker_counts_losses() {
+        let stats = counted(&[0, 1, 4, 5]);
+        assert_eq!(stats, LinkStats { received: 4, dropped: 2, out_of_order: 0, duplicated: 0 });
+        // the first one seen may be any
+        assert_eq!(counted(&[10, 11]).dropped, 0);
+    }
+
+    #[test]
+    fn seq_tracker_counts_late_ones_as_not_dropped() {
+        let stats = counted(&[0, 2, 3, 1]);
+        assert_eq!(stats, LinkStats { received: 4, dropped: 0, out_of_order: 1, duplicated: 0 });
+    }
+
+    #[test]
+    fn seq_tracker_counts_duplicates() {
+        let stats = counted(&[0, 1, 1, 2, 0]);
+        assert_eq!(stats, LinkStats { received: 5, dropped: 0, out_of_order: 0, duplicated: 2 });
+        // a late one coming twice is out of order once, then doubled
+        let stats = counted(&[0, 2, 1, 1]);
+        assert_eq!(stats, LinkStats { received: 4, dropped: 0, out_of_order: 1, duplicated: 1 });
+    }
+
+    #[test]
+    fn seq_tracker_forgets_long_gone_ones() {
+        let stats = counted(&[0, MISSING_WINDOW * 3, 1]);
+        assert_eq!(stats.dropped, MISSING_WINDOW * 3 - 1);
+        assert_eq!(stats.duplicated, 1);
+    }
+
+    #[test]
+    fn claims_keep_players_to_their_connection() {
+        let claims = Claims::default();
+        let (a, b) = (claims.connect(), claims.connect());
+        assert!(claims.admit(a, &YardCtrl::NewSnake(1, String::from("Ann"), None)));
+        assert!(!claims.admit(b, &YardCtrl::NewSnake(1, String::from("Bob"), None)));
+        assert!(!claims.admit(b, &YardCtrl::CtrlSnake(1, Direction::L)));
+        assert!(!claims.admit(b, &YardCtrl::Chat(1, String::from("hi"))));
+        assert!(!claims.admit(b, &YardCtrl::LeaveGame(1)));
+        assert!(claims.admit(b, &YardCtrl::Ping(3)));
+        assert!(claims.admit(a, &YardCtrl::Chat(1, String::from("hi"))));
+        assert_eq!(claims.disconnect(a), vec![1]);
+        assert!(claims.admit(b, &YardCtrl::NewSnake(1, String::from("Bob"), None)));
+        claims.release(1);
+        assert!(!claims.admit(b, &YardCtrl::Boost(1)));
+    }
+}
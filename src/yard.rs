@@ -5,18 +5,24 @@
 
 pub use crate::render::{
     HEAD_L, HEAD_R, HEAD_U, HEAD_D, BEAN, FENCE, EMPTY,
+    ITEM_SPEED, ITEM_GHOST, ITEM_SHRINK, ITEM_MULTIPLIER, ITEM_SHIELD, BOOST_TAG,
     TUIBlock, YardBuf,
 };
+pub use crate::map::YardMap;
+use crate::map::{ MapError, MIN_MAP_SIZE, MAX_MAP_WIDTH, MAX_MAP_HEIGHT };
 
 pub use std::collections::VecDeque;
 
 pub use crossterm::style::Color;
 use serde::{Deserialize, Serialize};
 use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use std::fmt;
 
 /// coordinate on the field as (row, column)
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
-pub struct Coord(usize, usize);
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Coord(pub usize, pub usize);
 
 /// left, right, up, down
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -26,13 +32,17 @@ use Direction::{ L, R, U, D, };
 impl Direction {
     /// find a next random value for Direction, can't turn back
     pub fn next_random(self) -> Self {
+        self.next_random_with(&mut thread_rng())
+    }
+    /// like `next_random`, drawing from the given generator
+    pub fn next_random_with<R: Rng>(self, rng: &mut R) -> Self {
         match self {
             L => [R, U, D],
             R => [L, U, D],
             U => [L, R, D],
             D => [L, R, U],
         }
-        .choose(&mut thread_rng())
+        .choose(rng)
         .copied().unwrap()
     }
     /// returns the opposite direction, useful when judging valid moves
@@ -59,9 +69,29 @@ impl Coord {
             _ => None,
         }
     }
+    /// like `move_toward`, but leaving one edge enters from the opposite edge
+    pub fn wrap_toward(&self, d: Direction, bounds: Coord) -> Coord {
+        match d {
+            L => Coord(self.0, (self.1 + bounds.1 - 1) % bounds.1),
+            R => Coord(self.0, (self.1 + 1) % bounds.1),
+            U => Coord((self.0 + bounds.0 - 1) % bounds.0, self.1),
+            D => Coord((self.0 + 1) % bounds.0, self.1),
+        }
+    }
+    /// one block toward `d`, in a wrap-around arena or a fenced one
+    pub fn step(&self, d: Direction, bounds: Coord, wrap: bool) -> Option<Coord> {
+        if wrap {
+            Some(self.wrap_toward(d, bounds))
+        } else {
+            self.move_toward(d, bounds)
+        }
+    }
     /// call `bound.rand_inside()`, return a Coord inside the (0,0)-bound rectangle
     pub fn rand_inside(&self) -> Coord {
-        let mut rng = thread_rng();
+        self.rand_inside_with(&mut thread_rng())
+    }
+    /// like `rand_inside`, drawing from the given generator
+    pub fn rand_inside_with<R: Rng>(&self, rng: &mut R) -> Coord {
         Coord(rng.gen_range(0..self.0), rng.gen_range(0..self.1))
     }
 }
@@ -75,16 +105,342 @@ pub const MAX_PLAYERS: u8 = 5;
 pub enum YardBlockType {
     Empty,
     Bean,
+    Wall,
+    Item(PowerUp),
     Body(u8),
     Head(u8, Direction),
 }
-use YardBlockType::{ Empty, Bean, Body, Head };
+use YardBlockType::{ Empty, Bean, Wall, Item, Body, Head };
+
+/// pickups besides beans, giving the eater an effect
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PowerUp {
+    /// moves a block more each tick for a while
+    Speed,
+    /// goes through bodies for a while
+    Ghost,
+    /// every other snake loses some of its tail
+    Shrink,
+    /// beans are worth double for a while
+    Multiplier,
+    /// survives the next collision
+    Shield,
+}
+
+pub const POWER_UPS: [PowerUp; 5]
+    = [PowerUp::Speed, PowerUp::Ghost, PowerUp::Shrink, PowerUp::Multiplier, PowerUp::Shield];
+/// at most this many power-ups lie on the field
+pub const MAX_ITEMS: usize = 2;
+/// every tick a power-up appears with the chance of 1 / ITEM_CHANCE
+pub const ITEM_CHANCE: u32 = 30;
+/// how long timed effects last
+pub const EFFECT_TICKS: u64 = 50;
+/// how many blocks the shrink power-up takes from the others
+pub const SHRINK_LEN: usize = 3;
+
+/// speeds are counted in parts of a block, a snake at this speed moves a block each tick
+pub const SPEED_UNIT: u32 = 4;
+/// no snake goes faster than this, whatever it has
+pub const MAX_SPEED: u32 = 4 * SPEED_UNIT;
+/// boosting adds this much speed
+pub const BOOST_SPEED: u32 = SPEED_UNIT / 2;
+/// a boost costs this many blocks of length, and as many points
+pub const BOOST_COST: usize = 2;
+/// how long a boost lasts
+pub const BOOST_TICKS: u64 = 20;
+/// boosts add up till this many ticks
+pub const MAX_BOOST_TICKS: u64 = 3 * BOOST_TICKS;
+
+/// timed effects on a snake, as ticks left
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Effects {
+    pub speed: u64,
+    /// paid for with length, see `YardSim::boost_snake`
+    pub boost: u64,
+    pub ghost: u64,
+    pub multiplier: u64,
+    pub shield: bool,
+}
+
+impl Effects {
+    /// count down the timed effects by a tick
+    pub fn wear_off(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+        self.boost = self.boost.saturating_sub(1);
+        self.ghost = self.ghost.saturating_sub(1);
+        self.multiplier = self.multiplier.saturating_sub(1);
+    }
+
+    /// short tags of active effects, to be shown on the board
+    pub fn describe(&self) -> String {
+        let mut tags = String::new();
+        for (active, tag) in [
+            (self.speed > 0, ITEM_SPEED),
+            (self.boost > 0, BOOST_TAG),
+            (self.ghost > 0, ITEM_GHOST),
+            (self.multiplier > 0, ITEM_MULTIPLIER),
+            (self.shield, ITEM_SHIELD),
+        ] {
+            if active {
+                tags.push(' ');
+                tags.push_str(tag);
+            }
+        }
+        tags
+    }
+}
 
 pub const PLAYER_COLOR_MAP: [Color; MAX_PLAYERS as usize]
     = [Color::DarkGrey, Color::DarkRed, Color::DarkBlue, Color::DarkMagenta, Color::DarkCyan];
 
+/// at most this many teams in a game
+pub const MAX_TEAMS: u8 = 4;
+pub const TEAM_NAMES: [&str; MAX_TEAMS as usize] = ["Red", "Blue", "Magenta", "Cyan"];
+/// members of a team are colored in related shades, the first one is the team color
+pub const TEAM_COLOR_MAP: [[Color; MAX_PLAYERS as usize]; MAX_TEAMS as usize] = [
+    [Color::DarkRed, Color::Red, Color::Rgb { r: 255, g: 120, b: 120 },
+        Color::Rgb { r: 120, g: 0, b: 0 }, Color::Rgb { r: 200, g: 60, b: 40 }],
+    [Color::DarkBlue, Color::Blue, Color::Rgb { r: 120, g: 140, b: 255 },
+        Color::Rgb { r: 0, g: 0, b: 120 }, Color::Rgb { r: 40, g: 80, b: 200 }],
+    [Color::DarkMagenta, Color::Magenta, Color::Rgb { r: 255, g: 140, b: 255 },
+        Color::Rgb { r: 100, g: 0, b: 100 }, Color::Rgb { r: 180, g: 60, b: 160 }],
+    [Color::DarkCyan, Color::Cyan, Color::Rgb { r: 140, g: 255, b: 255 },
+        Color::Rgb { r: 0, g: 100, b: 100 }, Color::Rgb { r: 40, g: 180, b: 160 }],
+];
+
+/// how a game is won, played on top of the simulation
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MatchMode {
+    /// the classic game, snakes come and go and it never ends
+    Endless,
+    /// a round lasts for the seconds, the top scorer wins
+    TimeLimit(u64),
+    /// the first snake reaching the score wins
+    ScoreTarget(usize),
+    /// the round ends when at most one snake is alive
+    LastStanding,
+}
+
+/// how smart a bot is
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BotLevel {
+    /// a random walker, turning now and then
+    Easy,
+    /// a greedy bean-seeker, heading to the nearest bean
+    Normal,
+    /// a pathfinder, taking the shortest safe path to beans
+    Hard,
+}
+
+pub const BOT_LEVELS: [BotLevel; 3] = [BotLevel::Easy, BotLevel::Normal, BotLevel::Hard];
+
+impl BotLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BotLevel::Easy => "Easy",
+            BotLevel::Normal => "Normal",
+            BotLevel::Hard => "Hard",
+        }
+    }
+}
+
+/// how the game speeds up, if it does
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SpeedUp {
+    /// the same speed all the time
+    Steady,
+    /// faster as the longest snake grows
+    Growth,
+    /// faster as the round goes on
+    Time,
+}
+
+/// the simulation runs at this rate, unless the game is configured otherwise
+pub const DEFAULT_TICK_RATE: u64 = 10;
+pub const MIN_TICK_RATE: u64 = 2;
+pub const MAX_TICK_RATE: u64 = 30;
+
+/// options of a game, chosen by the host and shared with the clients
+/// anything missing when deserialized takes the default
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub width: usize,
+    pub height: usize,
+    pub bean_count: usize,
+    pub init_snake_len: usize,
+    /// toroidal arena, moving off one edge enters from the opposite edge
+    pub wrap_around: bool,
+    /// obstacles, if any, the yard takes the size of the map instead
+    pub map: Option<YardMap>,
+    /// power-ups that may appear on the field, none for the classic game
+    pub power_ups: Vec<PowerUp>,
+    /// how the game is won, played by the server
+    pub match_mode: MatchMode,
+    /// number of teams, less than 2 for free for all
+    pub teams: u8,
+    /// whether hitting a teammate's body kills, otherwise passes through
+    pub friendly_fire: bool,
+    /// computer controlled players, they make room for people joining
+    pub bots: u8,
+    pub bot_level: BotLevel,
+    /// ticks per second to begin with
+    pub tick_rate: u64,
+    pub speed_up: SpeedUp,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            width: 30,
+            height: 20,
+            bean_count: 5,
+            init_snake_len: 3,
+            wrap_around: false,
+            map: None,
+            power_ups: Vec::new(),
+            match_mode: MatchMode::Endless,
+            teams: 0,
+            friendly_fire: true,
+            bots: 0,
+            bot_level: BotLevel::Normal,
+            tick_rate: DEFAULT_TICK_RATE,
+            speed_up: SpeedUp::Steady,
+        }
+    }
+}
+
+/// reasons why a game config can't be played
+#[derive(Debug)]
+pub enum ConfigError {
+    /// the map can't be played, or has no room for the beans and snakes
+    Map(MapError),
+    /// width and height of the open field
+    BadSize(usize, usize),
+    /// free blocks of the open field, and blocks needed for the beans and every player's snake
+    NoRoom(usize, usize),
+    /// the length of newborn snakes
+    BadSnakeLen(usize),
+    /// ticks per second
+    BadTickRate(u64),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Map(e)
+                => write!(f, "{}", e),
+            ConfigError::BadSize(w, h)
+                => write!(
+                    f, "the field is {}x{}, it shall be between {}x{} and {}x{}",
+                    w, h, MIN_MAP_SIZE, MIN_MAP_SIZE, MAX_MAP_WIDTH, MAX_MAP_HEIGHT,
+                ),
+            ConfigError::NoRoom(free, needed)
+                => write!(f, "the field has {} blocks, the beans and snakes need {}", free, needed),
+            ConfigError::BadSnakeLen(len)
+                => write!(f, "snakes are born {} blocks long, it shall be from 1 to {}", len, MIN_MAP_SIZE),
+            ConfigError::BadTickRate(rate)
+                => write!(f, "{} ticks per second, it shall be from {} to {}", rate, MIN_TICK_RATE, MAX_TICK_RATE),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl GameConfig {
+    /// whether the game can be played, for configs that were not chosen in the menu, such as in the settings
+    pub fn check(&self) -> Result<(), ConfigError> {
+        // a newborn fits on the smallest field
+        if !(1..=MIN_MAP_SIZE).contains(&self.init_snake_len) {
+            return Err(ConfigError::BadSnakeLen(self.init_snake_len));
+        }
+        match &self.map {
+            Some(map) => {
+                map.check().map_err(ConfigError::Map)?;
+                map.check_room(self.bean_count, self.init_snake_len).map_err(ConfigError::Map)?;
+            },
+            None => {
+                if !(MIN_MAP_SIZE..=MAX_MAP_WIDTH).contains(&self.width)
+                    || !(MIN_MAP_SIZE..=MAX_MAP_HEIGHT).contains(&self.height) {
+                    return Err(ConfigError::BadSize(self.width, self.height));
+                }
+                let needed = self.bean_count.saturating_add(self.init_snake_len * MAX_PLAYERS as usize);
+                if self.width * self.height < needed {
+                    return Err(ConfigError::NoRoom(self.width * self.height, needed));
+                }
+            },
+        }
+        if !(MIN_TICK_RATE..=MAX_TICK_RATE).contains(&self.tick_rate) {
+            return Err(ConfigError::BadTickRate(self.tick_rate));
+        }
+        Ok(())
+    }
+}
+
+/// a snake as seen from outside the simulation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SnakeState {
+    /// field id
+    pub id: u8,
+    /// from the head to the tail
+    pub body: Vec<Coord>,
+    pub direction: Direction,
+    pub score: usize,
+    pub team: Option<u8>,
+    pub effects: Effects,
+    /// in `SPEED_UNIT`s, so `SPEED_UNIT` is a block each tick
+    pub speed: u32,
+    /// newborns don't move yet
+    pub stalled: bool,
+}
+
+/// a semantic snapshot of the yard, for whoever reasons about the game instead of drawing it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct YardState {
+    pub tick: u64,
+    pub width: usize,
+    pub height: usize,
+    pub wrap_around: bool,
+    pub snakes: Vec<SnakeState>,
+    pub beans: Vec<Coord>,
+    pub walls: Vec<Coord>,
+    pub items: Vec<(Coord, PowerUp)>,
+}
+
+impl YardState {
+    /// where a head at `c` goes next, `None` if it hits the fence
+    pub fn next_pos(&self, c: Coord, d: Direction) -> Option<Coord> {
+        c.step(d, Coord(self.height, self.width), self.wrap_around)
+    }
+
+    pub fn snake(&self, id: u8) -> Option<&SnakeState> {
+        self.snakes.iter().find(|s| s.id == id)
+    }
+
+    /// blocks that kill the snake `me` when moved into on the next tick
+    /// snakes move in the order of ids, so tails of those before `me` have retracted
+    pub fn obstacles(&self, me: u8) -> Vec<Vec<bool>> {
+        let mut blocked = vec![vec![false; self.width]; self.height];
+        for c in &self.walls {
+            blocked[c.0][c.1] = true;
+        }
+        for s in &self.snakes {
+            let retracted = s.id < me && !s.stalled;
+            let len = if retracted { s.body.len() - 1 } else { s.body.len() };
+            for c in &s.body[..len] {
+                blocked[c.0][c.1] = true;
+            }
+        }
+        blocked
+    }
+}
+
 /// snakes which have a head and direction, the head is the front element
-pub struct Snake(VecDeque<Coord>, Direction);
+/// with the turns asked for but not taken yet, one is taken each step
+pub struct Snake(VecDeque<Coord>, Direction, VecDeque<Direction>);
+
+/// turns waiting for a snake at most, the ones beyond are dropped
+pub const MAX_PENDING_TURNS: usize = 3;
 
 /// game simulator
 pub struct YardSim {
@@ -93,15 +449,27 @@ pub struct YardSim {
     height: usize,
     bean_count: usize,
     init_snake_len: usize,
+    wrap_around: bool,
+    spawns: Vec<Coord>,                             // heads are born here if any
+    bean_zone: Vec<Coord>,                          // beans only grow here if any
+    power_ups: Vec<PowerUp>,
+    friendly_fire: bool,
     // running status
     tick: u64,
     beans_left: usize,
+    items_left: usize,
     block_map: Vec<Vec<YardBlockType>>,             // without borders, thus with shape w * h
     snakes: [Option<Snake>; MAX_PLAYERS as usize],  // there can be player ids not registered
     stall_protect: [u64; MAX_PLAYERS as usize],     // newborns shall have some ticks to stall
     score: [usize; MAX_PLAYERS as usize],
     failed: [bool; MAX_PLAYERS as usize],           // mark fail and clean up
     bonused: [usize; MAX_PLAYERS as usize],
+    effects: [Effects; MAX_PLAYERS as usize],
+    speed: [u32; MAX_PLAYERS as usize],             // own speed of each snake, without effects
+    progress: [u32; MAX_PLAYERS as usize],          // parts of a block moved but not stepped yet
+    team: [Option<u8>; MAX_PLAYERS as usize],
+    kills: Vec<(u8, u8)>,                           // who killed whom in the last tick
+    rng: StdRng,                                    // all the randomness of the game
 }
 
 impl YardSim {
@@ -111,14 +479,40 @@ impl YardSim {
     ///  - `bean_count`: the initial bean count, and it will hold forever
     ///  - `init_snake_len`: the length of the snake, don't make it large, otherwise hurts perf
     pub fn new(width: usize, height: usize, bean_count: usize, init_snake_len: usize) -> YardSim {
+        YardSim::from_config(&GameConfig {
+            width, height, bean_count, init_snake_len,
+            ..GameConfig::default()
+        })
+    }
+
+    /// create a simulator obeying all the options of a game
+    pub fn from_config(config: &GameConfig) -> YardSim {
+        YardSim::with_rng(config, StdRng::from_entropy())
+    }
+
+    /// like `from_config`, but the same seed with the same moves always plays the same game
+    pub fn with_seed(config: &GameConfig, seed: u64) -> YardSim {
+        YardSim::with_rng(config, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(config: &GameConfig, rng: StdRng) -> YardSim {
+        let (width, height) = (config.width, config.height);
         let mut block_map = Vec::<Vec<YardBlockType>>::new();
         for _row in 0..height {
             block_map.push(vec![Empty; width]);
         }
         let mut y = YardSim {
-                width, height, bean_count, init_snake_len,
+                width, height,
+                bean_count: config.bean_count,
+                init_snake_len: config.init_snake_len,
+                wrap_around: config.wrap_around,
+                spawns: Vec::new(),
+                bean_zone: Vec::new(),
+                power_ups: config.power_ups.clone(),
+                friendly_fire: config.friendly_fire,
                 tick: 0,
                 beans_left: 0,
+                items_left: 0,
                 block_map,
                 /// [None; MAX_PLAYERS as usize] won't work well, so make it clumsy
                 snakes: [None, None, None, None, None],
@@ -126,11 +520,43 @@ impl YardSim {
                 score: [0; MAX_PLAYERS as usize],
                 failed: [false; MAX_PLAYERS as usize],
                 bonused: [0; MAX_PLAYERS as usize],
+                effects: [Effects::default(); MAX_PLAYERS as usize],
+                speed: [SPEED_UNIT; MAX_PLAYERS as usize],
+                progress: [0; MAX_PLAYERS as usize],
+                team: [None; MAX_PLAYERS as usize],
+                kills: Vec::new(),
+                rng,
             };
+        if let Some(map) = &config.map {
+            y.load_map(map);
+        }
         y.fill_beans(); // tries to generate beans
         y
     }
 
+    /// reset the yard to the shape of the map, with walls, spawn points and bean zones
+    /// snakes on the field are all gone, so load before registering snakes
+    pub fn load_map(&mut self, map: &YardMap) {
+        self.width = map.width;
+        self.height = map.height;
+        self.block_map = vec![vec![Empty; map.width]; map.height];
+        for c in &map.walls {
+            self.block_map[c.0][c.1] = Wall;
+        }
+        self.spawns = map.spawns.clone();
+        self.bean_zone = map.bean_zone.clone();
+        self.snakes = [None, None, None, None, None];
+        self.score = [0; MAX_PLAYERS as usize];
+        self.failed = [false; MAX_PLAYERS as usize];
+        self.bonused = [0; MAX_PLAYERS as usize];
+        self.effects = [Effects::default(); MAX_PLAYERS as usize];
+        self.speed = [SPEED_UNIT; MAX_PLAYERS as usize];
+        self.progress = [0; MAX_PLAYERS as usize];
+        self.team = [None; MAX_PLAYERS as usize];
+        self.beans_left = 0;
+        self.items_left = 0;
+    }
+
     /// generate a buffer for the client to print
     pub fn generate_buf(&self) -> YardBuf {
         let mut result_buf = YardBuf::new();
@@ -150,18 +576,36 @@ impl YardSim {
                             bg: Color::Green,
                             content: BEAN.to_string(),
                         }),
+                    Wall
+                        => Some(TUIBlock {
+                            fg: Color::Black,
+                            bg: Color::Grey,
+                            content: FENCE.to_string(),
+                        }),
+                    Item(p)
+                        => Some(TUIBlock {
+                            fg: Color::Black,
+                            bg: Color::Yellow,
+                            content: match p {
+                                PowerUp::Speed => ITEM_SPEED.to_string(),
+                                PowerUp::Ghost => ITEM_GHOST.to_string(),
+                                PowerUp::Shrink => ITEM_SHRINK.to_string(),
+                                PowerUp::Multiplier => ITEM_MULTIPLIER.to_string(),
+                                PowerUp::Shield => ITEM_SHIELD.to_string(),
+                            },
+                        }),
                     Body(id) if *id < MAX_PLAYERS
                         => Some(TUIBlock {
                             fg: Color::White,
                             bg: if self.stall_protect[*id as usize] & 1 > 0 { Color::White }
-                                else { PLAYER_COLOR_MAP[*id as usize] },
+                                else { self.color_of(*id) },
                             content: EMPTY.to_string(),
                         }),
                     Head(id, d) if *id < MAX_PLAYERS
                         => Some(TUIBlock {
                             fg: Color::White,
                             bg: if self.stall_protect[*id as usize] & 1 > 0 { Color::White }
-                                else { PLAYER_COLOR_MAP[*id as usize] },
+                                else { self.color_of(*id) },
                             content: match d {
                                 Direction::L => HEAD_L.to_string(),
                                 Direction::R => HEAD_R.to_string(),
@@ -177,7 +621,52 @@ impl YardSim {
         result_buf
     }
 
+    /// where a head at `c` goes next, `None` if it hits the fence
+    pub fn next_pos(&self, c: Coord, d: Direction) -> Option<Coord> {
+        c.step(d, Coord(self.height, self.width), self.wrap_around)
+    }
+
+    /// take a snapshot of everything on the field
+    pub fn snapshot(&self) -> YardState {
+        let mut state = YardState {
+            tick: self.tick,
+            width: self.width,
+            height: self.height,
+            wrap_around: self.wrap_around,
+            snakes: Vec::new(),
+            beans: Vec::new(),
+            walls: Vec::new(),
+            items: Vec::new(),
+        };
+        for (r, row) in self.block_map.iter().enumerate() {
+            for (c, block) in row.iter().enumerate() {
+                match block {
+                    Bean => { state.beans.push(Coord(r, c)); },
+                    Wall => { state.walls.push(Coord(r, c)); },
+                    Item(p) => { state.items.push((Coord(r, c), *p)); },
+                    _ => {},
+                }
+            }
+        }
+        for (id, s) in self.snakes.iter().enumerate() {
+            if let Some(s) = s {
+                state.snakes.push(SnakeState {
+                    id: id as u8,
+                    body: s.0.iter().copied().collect(),
+                    direction: s.1,
+                    score: self.score[id],
+                    team: self.team[id],
+                    effects: self.effects[id],
+                    speed: self.speed_of(id as u8),
+                    stalled: self.stall_protect[id] > 0,
+                });
+            }
+        }
+        state
+    }
+
     /// tries hard to create a snake on the field, return a id
+    /// `None` if all the ids are taken or there is no room left
     pub fn init_snake(&mut self) -> Option<u8> {
         // find a id to assign
         let mut i: u8 = 0;
@@ -194,36 +683,11 @@ impl YardSim {
         if id == None {
             return id;
         }
-        // generate snake
-        let bound = Coord(self.height, self.width);
-        let mut segment: VecDeque<Coord>;
-        let mut d;
-        'choose_segment:
-        loop {
-            let mut tail = bound.rand_inside();
-            d = [L, R, U, D].choose(&mut thread_rng()).copied().unwrap();
-            segment = VecDeque::<Coord>::new();
-            segment.push_front(tail);
-            for _i in 1..self.init_snake_len {
-                match tail.move_toward(d, bound) {
-                    Some(c) => {
-                        segment.push_front(c);
-                        tail = c;
-                    },
-                    None => {
-                        continue 'choose_segment;
-                    },
-                }
-            }
-            // judge if intersect with others
-            for c in &segment {
-                match self.block_map[c.0][c.1] {
-                    Empty => continue,
-                    _ => continue 'choose_segment,
-                }
-            }
-            break; // finished nicely
-        }
+        // generate snake, on a spawn point if possible
+        let (segment, d) = match self.segment_on_spawn() {
+            Some(found) => found,
+            None => self.random_segment()?,
+        };
         // register snake
         let head = segment.front().unwrap();
         self.block_map[head.0][head.1] = Head(id.unwrap(), d);
@@ -232,47 +696,263 @@ impl YardSim {
         for c in iter {
             self.block_map[c.0][c.1] = Body(id.unwrap());
         }
-        self.snakes[id.unwrap() as usize] = Some(Snake(segment, d));
+        self.snakes[id.unwrap() as usize] = Some(Snake(segment, d, VecDeque::new()));
         self.score[id.unwrap() as usize] = self.init_snake_len;
+        self.progress[id.unwrap() as usize] = 0;
         self.stall_protect[id.unwrap() as usize] = 10; // set protection to 10 ticks
         id
     }
 
+    /// tries hard to find a free segment anywhere on the field, `None` if there is no room
+    fn random_segment(&mut self) -> Option<(VecDeque<Coord>, Direction)> {
+        let bound = Coord(self.height, self.width);
+        for _try in 0..(self.width * self.height) {
+            let tail = bound.rand_inside_with(&mut self.rng);
+            let d = [L, R, U, D].choose(&mut self.rng).copied().unwrap();
+            if let Some(segment) = self.free_segment(tail, d) {
+                return Some((segment, d));
+            }
+        }
+        // the field is crowded, so every place is tried
+        let mut places = Vec::new();
+        for r in 0..self.height {
+            for c in 0..self.width {
+                for d in [L, R, U, D] {
+                    if self.free_segment(Coord(r, c), d).is_some() {
+                        places.push((Coord(r, c), d));
+                    }
+                }
+            }
+        }
+        let (tail, d) = places.choose(&mut self.rng).copied()?;
+        Some((self.free_segment(tail, d)?, d))
+    }
+
+    /// a snake from `tail` heading toward `d`, if all its blocks are inside and empty
+    fn free_segment(&self, mut tail: Coord, d: Direction) -> Option<VecDeque<Coord>> {
+        let bound = Coord(self.height, self.width);
+        let mut segment = VecDeque::<Coord>::new();
+        segment.push_front(tail);
+        for _i in 1..self.init_snake_len {
+            tail = tail.move_toward(d, bound)?;
+            segment.push_front(tail);
+        }
+        match segment.iter().all(|c| matches!(self.block_map[c.0][c.1], Empty)) {
+            true => Some(segment),
+            false => None,
+        }
+    }
+
+    /// a random empty block, `None` if the field is full
+    fn random_empty_block(&mut self) -> Option<Coord> {
+        let bound = Coord(self.height, self.width);
+        for _try in 0..(self.width * self.height) {
+            let c = bound.rand_inside_with(&mut self.rng);
+            if matches!(self.block_map[c.0][c.1], Empty) {
+                return Some(c);
+            }
+        }
+        // the field is crowded, so the empty blocks are listed
+        let free: Vec<Coord> = (0..self.height)
+            .flat_map(|r| (0..self.width).map(move |c| Coord(r, c)))
+            .filter(|c| matches!(self.block_map[c.0][c.1], Empty))
+            .collect();
+        free.choose(&mut self.rng).copied()
+    }
+
+    /// mark a snake fail, it is cleaned up after the next tick
+    pub fn kill_snake(&mut self, id: u8) {
+        if self.snakes[id as usize].is_some() {
+            self.failed[id as usize] = true;
+        }
+    }
+
+    /// take a snake off the field right away, making room for another
+    pub fn remove_snake(&mut self, id: u8) {
+        if let Some(s) = self.snakes[id as usize].take() {
+            for each_pos in s.0 {
+                self.vacate(each_pos);
+            }
+            self.score[id as usize] = 0;
+            self.failed[id as usize] = false;
+            self.bonused[id as usize] = 0;
+            self.effects[id as usize] = Effects::default();
+            self.speed[id as usize] = SPEED_UNIT;
+            self.team[id as usize] = None;
+        }
+    }
+
+    /// find a free segment with its head on one of the spawn points
+    fn segment_on_spawn(&mut self) -> Option<(VecDeque<Coord>, Direction)> {
+        let bound = Coord(self.height, self.width);
+        let mut spawns = self.spawns.clone();
+        spawns.shuffle(&mut self.rng);
+        for head in spawns {
+            let mut dirs = [L, R, U, D];
+            dirs.shuffle(&mut self.rng);
+            'try_direction:
+            for d in dirs {
+                // the body lies behind the head
+                let mut segment = VecDeque::<Coord>::new();
+                let mut tail = head;
+                segment.push_back(head);
+                for _i in 1..self.init_snake_len {
+                    match tail.move_toward(d.opposite(), bound) {
+                        Some(c) => {
+                            segment.push_back(c);
+                            tail = c;
+                        },
+                        None => continue 'try_direction,
+                    }
+                }
+                if segment.iter().all(|c| matches!(self.block_map[c.0][c.1], Empty)) {
+                    return Some((segment, d));
+                }
+            }
+        }
+        None
+    }
+
+    /// queue a turn, so quick turns within a tick are taken one by one in the next ticks
     pub fn control_snake(&mut self, id: u8, d: Direction) -> Option<()> {
         match &mut self.snakes[id as usize] {
             Some(s) => {
-                let head = s.0.front().unwrap();
-                // can't turn back, regarding the state before ticking
-                match self.block_map[head.0][head.1] {
-                    Head(bid, cur_dir) => {
-                        if bid == id && d != cur_dir.opposite() {
-                            s.1 = d;
-                        }
-                    },
-                    _ => {},
-                };
+                // can't turn back, regarding the turn before this one
+                let last = s.2.back().copied().unwrap_or(s.1);
+                if d != last && d != last.opposite() && s.2.len() < MAX_PENDING_TURNS {
+                    s.2.push_back(d);
+                }
                 Some(())
             },
             None => None,
         }
     }
 
+    /// trade `BOOST_COST` blocks of the tail, and as many points, for `BOOST_TICKS` of extra speed
+    /// returns whether the snake could afford it
+    pub fn boost_snake(&mut self, id: u8) -> bool {
+        let i = id as usize;
+        let tails = match &mut self.snakes[i] {
+            Some(s) if s.0.len() > BOOST_COST && self.effects[i].boost + BOOST_TICKS <= MAX_BOOST_TICKS => {
+                let at = s.0.len() - BOOST_COST;
+                s.0.split_off(at)
+            },
+            _ => return false,
+        };
+        for tail in tails {
+            self.vacate(tail);
+        }
+        // a snake on the field always has some score
+        self.score[i] = self.score[i].saturating_sub(BOOST_COST).max(1);
+        self.effects[i].boost += BOOST_TICKS;
+        true
+    }
+
+    /// set the own speed of a snake in `SPEED_UNIT`s, effects go on top of it
+    pub fn set_speed(&mut self, id: u8, speed: u32) {
+        self.speed[id as usize] = speed.clamp(1, MAX_SPEED);
+    }
+
+    /// how fast a snake goes now in `SPEED_UNIT`s, with its effects
+    pub fn speed_of(&self, id: u8) -> u32 {
+        let i = id as usize;
+        let mut speed = self.speed[i];
+        if self.effects[i].speed > 0 {
+            speed += SPEED_UNIT;
+        }
+        if self.effects[i].boost > 0 {
+            speed += BOOST_SPEED;
+        }
+        speed.min(MAX_SPEED)
+    }
+
     /// produce new beans on the ground till satisfied, please do after ticks
     pub fn fill_beans(&mut self) {
         while self.beans_left < self.bean_count {
-            let bound = Coord(self.height, self.width);
-            let loc = loop {
-                let c = bound.rand_inside();
-                match &self.block_map[c.0][c.1] {
-                    Empty => { break c; },
-                    _ => {},
-                };
+            let loc = if !self.bean_zone.is_empty() {
+                let free: Vec<&Coord> = self.bean_zone.iter()
+                    .filter(|c| matches!(self.block_map[c.0][c.1], Empty))
+                    .collect();
+                match free.choose(&mut self.rng) {
+                    Some(c) => **c,
+                    None => { break; }, // zones are full, wait for next time
+                }
+            } else {
+                match self.random_empty_block() {
+                    Some(c) => c,
+                    None => { break; }, // the field is full, wait for next time
+                }
             };
             self.block_map[loc.0][loc.1] = Bean;
             self.beans_left += 1;
         }
     }
 
+    /// produce a random enabled power-up now and then, please do after ticks
+    pub fn fill_items(&mut self) {
+        if self.power_ups.is_empty() || self.items_left >= MAX_ITEMS
+            || !self.rng.gen_ratio(1, ITEM_CHANCE) {
+            return;
+        }
+        let bound = Coord(self.height, self.width);
+        for _try in 0..(self.width * self.height) {
+            let c = bound.rand_inside_with(&mut self.rng);
+            if matches!(self.block_map[c.0][c.1], Empty) {
+                self.block_map[c.0][c.1] = Item(*self.power_ups.choose(&mut self.rng).unwrap());
+                self.items_left += 1;
+                return;
+            }
+        }
+    }
+
+    /// put a snake into a team, `None` for on its own
+    pub fn set_team(&mut self, id: u8, team: Option<u8>) {
+        self.team[id as usize] = team.filter(|t| *t < MAX_TEAMS);
+    }
+
+    pub fn team_of(&self, id: u8) -> Option<u8> {
+        self.team[id as usize]
+    }
+
+    /// the color of a snake, teammates are in related shades
+    pub fn color_of(&self, id: u8) -> Color {
+        match self.team[id as usize] {
+            Some(t) => {
+                let shade = (0..id).filter(|i| self.team[*i as usize] == Some(t)).count();
+                TEAM_COLOR_MAP[t as usize][shade]
+            },
+            None => PLAYER_COLOR_MAP[id as usize],
+        }
+    }
+
+    /// whether two snakes are teammates
+    fn teammates(&self, a: u8, b: u8) -> bool {
+        a != b && self.team[a as usize].is_some() && self.team[a as usize] == self.team[b as usize]
+    }
+
+    /// the effects a snake currently has
+    pub fn effects(&self, id: u8) -> Effects {
+        self.effects[id as usize]
+    }
+
+    /// a position is left by a snake, reveal what else is there
+    /// note that ghosting snakes may overlap with bodies
+    fn vacate(&mut self, c: Coord) {
+        self.block_map[c.0][c.1] = Empty;
+        for (id, s) in self.snakes.iter().enumerate() {
+            if let Some(s) = s {
+                if let Some(i) = s.0.iter().position(|p| p.0 == c.0 && p.1 == c.1) {
+                    self.block_map[c.0][c.1] = if i == 0 { Head(id as u8, s.1) } else { Body(id as u8) };
+                }
+            }
+        }
+    }
+
+    /// snakes killed by others in the last tick, as field ids of the attacker and the victim
+    pub fn kills(&self) -> &[(u8, u8)] {
+        &self.kills
+    }
+
     /// clean up failed snakes, please do after ticks
     pub fn cleanup(&mut self) {
         for id in 0..(MAX_PLAYERS as usize) {
@@ -281,78 +961,250 @@ impl YardSim {
                 self.bonused[id] = 0;
             }
             if self.failed[id] {
-                // clean up mess
-                for each_pos in &self.snakes[id].as_ref().unwrap().0 {
-                    self.block_map[each_pos.0][each_pos.1] = Empty;
+                // unregister a snake, and clean up mess
+                let s = self.snakes[id].take().unwrap();
+                for each_pos in s.0 {
+                    self.vacate(each_pos);
                 }
-                // unregister a snake
                 self.score[id] = 0;
-                self.snakes[id] = None;
                 self.failed[id] = false;
+                self.effects[id] = Effects::default();
+                self.speed[id] = SPEED_UNIT;
+                self.team[id] = None;
             }
         }
     }
-    
+
+    /// a snake bumps into something, `attacker` gets bonused if it is a snake
+    /// a shield saves the snake once, staying where it is
+    fn collide(&mut self, id: u8, attacker: Option<u8>) {
+        let effects = &mut self.effects[id as usize];
+        if effects.shield {
+            effects.shield = false;
+            return;
+        }
+        self.failed[id as usize] = true;
+        if let Some(id_at) = attacker {
+            self.bonused[id_at as usize] += self.score[id as usize];
+            self.kills.push((id_at, id));
+        }
+    }
+
+    /// the eater gets what the power-up does
+    fn apply_power_up(&mut self, id: u8, p: PowerUp) {
+        let effects = &mut self.effects[id as usize];
+        match p {
+            PowerUp::Speed => { effects.speed = EFFECT_TICKS; },
+            PowerUp::Ghost => { effects.ghost = EFFECT_TICKS; },
+            PowerUp::Multiplier => { effects.multiplier = EFFECT_TICKS; },
+            PowerUp::Shield => { effects.shield = true; },
+            PowerUp::Shrink => {
+                for other in 0..MAX_PLAYERS {
+                    if other == id {
+                        continue;
+                    }
+                    for _i in 0..SHRINK_LEN {
+                        let tail = match &mut self.snakes[other as usize] {
+                            Some(s) if s.0.len() > 1 => s.0.pop_back().unwrap(),
+                            _ => break,
+                        };
+                        self.vacate(tail);
+                    }
+                }
+            },
+        }
+    }
+
+    /// move a snake by one block, and decide if it gets point or fails
+    fn step_snake(&mut self, id: u8) {
+        let (head, d) = match &mut self.snakes[id as usize] {
+            Some(s) => {
+                // take the next turn
+                if let Some(turn) = s.2.pop_front() {
+                    if turn != s.1.opposite() {
+                        s.1 = turn;
+                    }
+                }
+                (*s.0.front().unwrap(), s.1)
+            },
+            None => return,
+        };
+        let new_head = match self.next_pos(head, d) {
+            Some(pos) => pos,
+            None => {
+                self.collide(id, None);
+                return;
+            },
+        };
+        // going to one's retracting nail would fail, mark and cleanup strategy
+        let grow = match self.block_map[new_head.0][new_head.1] {
+            Empty => false,             // go over and let tail retract
+            Bean => {                   // go over extending head
+                self.bonused[id as usize] += if self.effects[id as usize].multiplier > 0 { 2 } else { 1 };
+                self.beans_left -= 1;
+                true
+            },
+            Item(p) => {
+                self.items_left -= 1;
+                self.apply_power_up(id, p);
+                false
+            },
+            Wall => {
+                self.collide(id, None);
+                return;
+            },
+            Body(_id_at) if self.effects[id as usize].ghost > 0 => false, // pass through
+            Body(id_at) if self.teammates(id, id_at) && !self.friendly_fire => false,
+            Body(id_at) if self.teammates(id, id_at) => {
+                self.collide(id, None);                         // no bonus for that
+                return;
+            },
+            Body(id_at) if id_at == id => {
+                self.collide(id, None);                         // biting oneself is no kill
+                return;
+            },
+            Body(id_at) => {
+                self.collide(id, Some(id_at));                  // bonus the attacker
+                return;
+            },
+            Head(id_at, _d) => {        // judge if head-to-head collision
+                if id < id_at {
+                    self.collide(id, Some(id_at));
+                } else {
+                    self.collide(id, None);
+                    self.collide(id_at, None);
+                }
+                return;
+            },
+        };
+        self.block_map[head.0][head.1] = Body(id);
+        self.block_map[new_head.0][new_head.1] = Head(id, d);
+        let s = self.snakes[id as usize].as_mut().unwrap();
+        s.0.push_front(new_head);
+        if !grow {
+            let tail = s.0.pop_back().unwrap();
+            self.vacate(tail);
+        }
+    }
+
     /// simulate the game:
     ///  - update each snake's position by its direction
     ///  - decide if gets point or fails
     pub fn next_tick(&mut self) -> ([usize; MAX_PLAYERS as usize], [bool; MAX_PLAYERS as usize]) {
+        self.kills.clear();
         for id in 0..MAX_PLAYERS {
             // handle newborn protection
             if self.stall_protect[id as usize] > 0 {
                 self.stall_protect[id as usize] -= 1;
                 continue;
             }
-            match &mut self.snakes[id as usize] {
-                Some(s) => {
-                    let head = s.0.front().unwrap();
-                    let new_head
-                        = match head.move_toward(s.1, Coord(self.height, self.width)) {
-                            Some(pos) => pos,
-                            None => {
-                                self.failed[id as usize] = true;
-                                continue;
-                            },
-                        };
-                    self.block_map[head.0][head.1] = Body(id);
-                    s.0.push_front(new_head);
-                    // going to one's retracting nail would fail, mark and cleanup strategy
-                    match &self.block_map[new_head.0][new_head.1] {
-                        Empty => {              // go over and let tail retract
-                            self.block_map[new_head.0][new_head.1] = Head(id, s.1);
-                            let tail = s.0.pop_back().unwrap();
-                            self.block_map[tail.0][tail.1] = Empty;
-                        },
-                        Bean => {               // go over extending head
-                            self.block_map[new_head.0][new_head.1] = Head(id, s.1);
-                            self.bonused[id as usize] += 1;
-                            self.beans_left -= 1;
-                        },
-                        Body(id_at) => {
-                            self.failed[id as usize] = true;                 // mark this snake fail
-                            self.bonused[*id_at as usize] += self.score[id as usize]; // bonus the attacker
-                            continue;
-                        },
-                        Head(id_at, _d) => {     // judge if head-to-head collision
-                            if id < *id_at {
-                                self.failed[id as usize] = true;
-                                self.bonused[*id_at as usize] += self.score[id as usize];
-                                continue;
-                            } else {
-                                self.failed[id as usize] = true;
-                                self.failed[*id_at as usize] = true;
-                                continue;
-                            }
-                        },
-                    }
-                },
-                None => continue,
+            if self.snakes[id as usize].is_none() {
+                continue;
+            }
+            // a step for each whole block moved, the rest waits for the next tick
+            self.progress[id as usize] += self.speed_of(id);
+            while self.progress[id as usize] >= SPEED_UNIT {
+                self.progress[id as usize] -= SPEED_UNIT;
+                if self.failed[id as usize] {
+                    break;
+                }
+                self.step_snake(id);
             }
+            self.effects[id as usize].wear_off();
         }
         self.tick += 1;
         let (score, failed) = (self.score.clone(), self.failed.clone());
         self.cleanup();
         self.fill_beans();
+        self.fill_items();
         (score, failed)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_can_be_played() {
+        assert!(GameConfig::default().check().is_ok());
+        for map in YardMap::builtins() {
+            assert!(GameConfig { map: Some(map), ..GameConfig::default() }.check().is_ok());
+        }
+    }
+
+    #[test]
+    fn check_tells_what_is_wrong() {
+        let config = |c: GameConfig| c.check().unwrap_err();
+        assert!(matches!(config(GameConfig { init_snake_len: 0, ..GameConfig::default() }), ConfigError::BadSnakeLen(0)));
+        assert!(matches!(config(GameConfig { width: 100, ..GameConfig::default() }), ConfigError::BadSize(100, 20)));
+        assert!(matches!(
+            config(GameConfig { width: 5, height: 5, bean_count: 20, ..GameConfig::default() }),
+            ConfigError::NoRoom(25, 35),
+        ));
+        assert!(matches!(config(GameConfig { tick_rate: 1, ..GameConfig::default() }), ConfigError::BadTickRate(1)));
+        let mut map = YardMap::builtins().remove(0);
+        map.walls.push(Coord(100, 0));
+        assert!(matches!(
+            config(GameConfig { map: Some(map), ..GameConfig::default() }),
+            ConfigError::Map(MapError::OffMap(Coord(100, 0))),
+        ));
+    }
+
+    #[test]
+    fn crowded_field_fills_up_without_hanging() {
+        let config = GameConfig { width: 5, height: 5, bean_count: 30, init_snake_len: 5, ..GameConfig::default() };
+        let mut y = YardSim::with_seed(&config, 3);
+        let mut snakes = 0;
+        while y.init_snake().is_some() {
+            snakes += 1;
+        }
+        assert!(snakes < MAX_PLAYERS as usize);
+        for _ in 0..10 {
+            y.next_tick();
+        }
+        let state = y.snapshot();
+        let taken: usize = state.beans.len() + state.snakes.iter().map(|s| s.body.len()).sum::<usize>();
+        assert!(taken <= 25);
+    }
+
+    #[test]
+    fn biting_oneself_is_no_kill() {
+        let config = GameConfig { init_snake_len: 6, wrap_around: true, ..GameConfig::default() };
+        let mut y = YardSim::with_seed(&config, 5);
+        let me = y.init_snake().unwrap();
+        let d = y.snapshot().snake(me).unwrap().direction;
+        let across = match d {
+            Direction::L | Direction::R => Direction::U,
+            Direction::U | Direction::D => Direction::L,
+        };
+        // round and into its own body
+        for turn in [across, d.opposite(), across.opposite()] {
+            y.control_snake(me, turn);
+        }
+        for _ in 0..20 {
+            let (_score, failed) = y.next_tick();
+            if failed[me as usize] {
+                assert!(y.kills().is_empty());
+                return;
+            }
+        }
+        panic!("the snake never bit itself");
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let play = || {
+            let mut y = YardSim::with_seed(&GameConfig::default(), 9);
+            y.init_snake();
+            y.init_snake();
+            for _ in 0..20 {
+                y.next_tick();
+            }
+            y.snapshot()
+        };
+        let (a, b) = (play(), play());
+        assert_eq!(a.beans, b.beans);
+        assert_eq!(a.snakes.iter().map(|s| &s.body).collect::<Vec<_>>(), b.snakes.iter().map(|s| &s.body).collect::<Vec<_>>());
+    }
 }
\ No newline at end of file